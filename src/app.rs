@@ -1,4 +1,29 @@
-use crate::{constants::DREAM_FILE, dream::{Dream, Intensity, Style}};
+use crate::{
+    config::Config,
+    crypt::{self, Key},
+    dates::{from_local, night_of, parse_when},
+    filter::Filter,
+    editor::TextEditor,
+    dream::{normalize_tag, parse_tags, Dream, Intensity, Style},
+    history::{history_path, Command, History},
+    import::{self, Candidate, Outcome},
+    paths::expand_home,
+    search::SearchIndex,
+    state::State,
+    stats::{Stats, Streaks},
+    store::{
+        backup_path, kept_copies, list_backups, preserve_corrupt, prune_backups, write_atomic, Change, DreamStore,
+    },
+};
+use chrono::{DateTime, Duration, FixedOffset, Local, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
 
 #[derive(PartialEq)]
 pub enum InputMode {
@@ -8,56 +33,862 @@ pub enum InputMode {
     ConfirmDelete,
     ConfirmQuit,
     ViewingDream,
+    Recovery,
+    Backups,
+    ConfirmRestore,
+    Tags,
+    Search,
+    Filter,
+    Calendar,
+    Stats,
+    ImportPath,
+    ImportPreview,
+}
+
+/// Order of the record cards. `Added` is the order dreams were recorded in,
+/// by `created_at`, so it does not depend on where a store puts an entry.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortMode {
+    #[default]
+    Added,
+    DateAsc,
+    DateDesc,
+    Intensity,
+    Frequency,
+    Style,
+    Length,
+    Modified,
+}
+
+impl SortMode {
+    const ALL: [SortMode; 8] = [
+        SortMode::Added,
+        SortMode::DateAsc,
+        SortMode::DateDesc,
+        SortMode::Intensity,
+        SortMode::Frequency,
+        SortMode::Style,
+        SortMode::Length,
+        SortMode::Modified,
+    ];
+
+    pub fn next(self) -> SortMode {
+        let position = SortMode::ALL.iter().position(|m| *m == self).unwrap_or(0);
+        SortMode::ALL[(position + 1) % SortMode::ALL.len()]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Added => "order added",
+            SortMode::DateAsc => "oldest first",
+            SortMode::DateDesc => "newest first",
+            SortMode::Intensity => "most intense first",
+            SortMode::Frequency => "most frequent first",
+            SortMode::Style => "style",
+            SortMode::Length => "longest first",
+            SortMode::Modified => "recently changed first",
+        }
+    }
+
+    /// Ties keep the order of the journal.
+    pub fn compare(self, a: &Dream, b: &Dream) -> Ordering {
+        match self {
+            SortMode::Added => a.created_at.cmp(&b.created_at),
+            SortMode::DateAsc => a.date.cmp(&b.date),
+            SortMode::DateDesc => b.date.cmp(&a.date),
+            SortMode::Intensity => b.intensity.cmp(&a.intensity),
+            SortMode::Frequency => b.frequency.cmp(&a.frequency),
+            SortMode::Style => a.style.to_string().cmp(&b.style.to_string()),
+            SortMode::Length => b.experience.chars().count().cmp(&a.experience.chars().count()),
+            SortMode::Modified => b.updated_at.cmp(&a.updated_at),
+        }
+    }
 }
 
 pub enum InputField {
+    Date,
     Intensity,
     Frequency,
     Style,
+    Tags,
     Experience,
     None,
 }
 
+/// Why the journal could not be loaded, with the parser's position when known.
+pub struct LoadError {
+    pub message: String,
+    pub position: Option<(usize, usize)>,
+}
+
+impl LoadError {
+    fn new(err: &(dyn Error + 'static)) -> LoadError {
+        LoadError {
+            message: err.to_string(),
+            position: err
+                .downcast_ref::<serde_json::Error>()
+                .map(|err| (err.line(), err.column())),
+        }
+    }
+}
+
 pub struct DreamApp {
     pub dreams: Vec<Dream>,
+    /// Indices into `dreams` of the records shown, in display order.
+    /// `selected` and `visible_start` index into this.
+    pub view: Vec<usize>,
+    pub filter: Option<Filter>,
+    pub filter_input: String,
+    /// Path typed in the import prompt.
+    pub import_input: String,
+    /// Records of the import being previewed.
+    pub import_candidates: Vec<Candidate>,
+    pub search: String,
+    /// Positions in `view` of the dreams matching `search`.
+    pub search_hits: Vec<usize>,
+    index: SearchIndex,
     pub input_mode: InputMode,
     pub input_field: InputField,
-    pub input: String,
+    pub editor: TextEditor,
+    pub tag_input: String,
+    /// What was typed in the date step; empty keeps the dream's date.
+    pub date_input: String,
+    /// Night picked in the calendar.
+    pub calendar_day: NaiveDate,
+    /// Shown on the stats screen; computed when it opens.
+    pub stats: Option<Stats>,
+    /// Streaks and the night they were counted up to; cleared when the
+    /// dreams change.
+    streaks: Option<(NaiveDate, Streaks)>,
     pub current_dream: Dream,
     pub selected: usize,
     pub visible_start: usize,
     pub selection_index: usize,
     pub frequency_value: u8,
-    pub editing_index: Option<usize>,
-    pub unsaved_changes: bool, 
+    pub editing_id: Option<Uuid>,
+    pub history: History,
+    pub store: Box<dyn DreamStore>,
+    pub pending: Vec<Change>,
+    pub load_error: Option<LoadError>,
+    pub backups: Vec<PathBuf>,
+    pub config: Config,
+    pub state: State,
+    pub status: Option<String>,
 }
 
 impl DreamApp {
-    pub fn new() -> DreamApp {
-        let dreams = match std::fs::read_to_string(DREAM_FILE) {
-            Ok(data) => serde_json::from_str(&data).unwrap_or_else(|_| Vec::new()),
-            Err(_) => Vec::new(),
+    pub fn new(mut store: Box<dyn DreamStore>, config: Config, state: State) -> DreamApp {
+        let (dreams, load_error) = match store.load() {
+            Ok(dreams) => (dreams, None),
+            Err(err) => (Vec::new(), Some(LoadError::new(err.as_ref()))),
+        };
+        let backups = list_backups(store.path());
+        let (history, status) = if config.persist_history && load_error.is_none() {
+            match History::load(store.path(), store.key(), &config.date_format) {
+                Ok(history) => (history, None),
+                Err(err) => (
+                    History::default(),
+                    Some(format!("Undo history could not be read: {}", err)),
+                ),
+            }
+        } else {
+            (History::default(), None)
         };
 
-        DreamApp {
+        let mut app = DreamApp {
             dreams,
-            input_mode: InputMode::Normal,
-            input_field: InputField::None,
-            input: String::new(),
-            current_dream: Dream {
-                date: "N/A".to_string(),
-                intensity: Intensity::Low,
-                experience: String::new(),
-                frequency: 0,
-                style: Style::Lucid,
+            view: Vec::new(),
+            filter: None,
+            filter_input: String::new(),
+            import_input: String::new(),
+            import_candidates: Vec::new(),
+            search: String::new(),
+            search_hits: Vec::new(),
+            index: SearchIndex::default(),
+            input_mode: if load_error.is_some() {
+                InputMode::Recovery
+            } else {
+                InputMode::Normal
             },
+            input_field: InputField::None,
+            editor: TextEditor::default(),
+            tag_input: String::new(),
+            date_input: String::new(),
+            calendar_day: Local::now().date_naive(),
+            stats: None,
+            streaks: None,
+            current_dream: Dream::new(Local::now().fixed_offset(), Intensity::Low, Style::Lucid),
             selected: 0,
             visible_start: 0,
             selection_index: 0,
             frequency_value: 0,
-            editing_index: None,
-            unsaved_changes: false,
+            editing_id: None,
+            history,
+            store,
+            pending: Vec::new(),
+            load_error,
+            backups,
+            config,
+            state,
+            status,
+        };
+        app.dreams_changed();
+        app
+    }
+
+    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.load_error.is_some() {
+            return Err("the journal failed to load; recover it before saving".into());
+        }
+        self.backup()?;
+        self.store.apply(&self.pending)?;
+        prune_backups(self.store.path(), self.config.backups)?;
+        self.pending.clear();
+        self.history.mark_saved();
+        if self.config.persist_history {
+            self.history
+                .save(self.store.path(), self.store.key())
+                .map_err(|err| format!("saved, but the undo history could not be written: {}", err))?;
+        }
+        Ok(())
+    }
+
+    /// Whether the journal differs from what was last saved or loaded.
+    pub fn unsaved_changes(&self) -> bool {
+        !self.history.is_saved()
+    }
+
+    pub fn add_dream(&mut self, mut dream: Dream) {
+        dream.created_at = chrono::Utc::now();
+        dream.updated_at = dream.created_at;
+        self.insert(dream.clone(), self.dreams.len());
+        self.history.push(Command::Add { dream });
+    }
+
+    /// Adds a dream read from another file, keeping its id and timestamps.
+    pub fn import_dream(&mut self, dream: Dream) {
+        self.insert(dream.clone(), self.dreams.len());
+        self.history.push(Command::Add { dream });
+    }
+
+    /// Reads the file or directory in `import_input` into
+    /// `import_candidates`, returning how many records it holds.
+    pub fn preview_import(&mut self) -> Result<usize, Box<dyn Error>> {
+        let path = expand_home(Path::new(self.import_input.trim()));
+        self.import_candidates = import::prepare(&[path], None, &BTreeMap::new(), &self.dreams, &self.config)?;
+        self.selection_index = 0;
+        Ok(self.import_candidates.len())
+    }
+
+    /// Adds the new dreams of the previewed import, returning how many.
+    pub fn commit_import(&mut self) -> usize {
+        let mut imported = 0;
+        for candidate in std::mem::take(&mut self.import_candidates) {
+            if let Outcome::New(dream) = candidate.outcome {
+                self.import_dream(dream);
+                imported += 1;
+            }
+        }
+        imported
+    }
+
+    /// Replaces the dream with the same id.
+    pub fn update_dream(&mut self, mut dream: Dream) {
+        dream.updated_at = chrono::Utc::now();
+        if let Some(before) = self.replace(dream.clone()) {
+            self.history.push(Command::Edit {
+                before,
+                after: dream,
+            });
+        }
+    }
+
+    pub fn delete_dream(&mut self, id: &Uuid) {
+        if let Some((dream, index)) = self.remove(id) {
+            self.history.push(Command::Delete { dream, index });
+        }
+    }
+
+    /// Reverses the newest command in the history.
+    pub fn undo(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(command) = self.history.undo() else {
+            self.status = Some("Nothing to undo".to_string());
+            return Ok(());
+        };
+        let applied = match command.clone() {
+            Command::Add { dream } => self.remove(&dream.id).is_some(),
+            Command::Edit { before, .. } => self.replace(before).is_some(),
+            Command::Delete { dream, index } => {
+                self.insert(dream, index);
+                true
+            }
+        };
+        self.history_applied(command, applied, "Undid")
+    }
+
+    /// Applies the newest undone command again.
+    pub fn redo(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(command) = self.history.redo() else {
+            self.status = Some("Nothing to redo".to_string());
+            return Ok(());
+        };
+        let applied = match command.clone() {
+            Command::Add { dream } => {
+                self.insert(dream, self.dreams.len());
+                true
+            }
+            Command::Edit { after, .. } => self.replace(after).is_some(),
+            Command::Delete { dream, .. } => self.remove(&dream.id).is_some(),
+        };
+        self.history_applied(command, applied, "Redid")
+    }
+
+    fn history_applied(&mut self, command: Command, applied: bool, verb: &str) -> Result<(), Box<dyn Error>> {
+        if !applied {
+            self.history = History::default();
+            self.history.mark_unsaved();
+            return Err("the undo history no longer matches the journal and was cleared".into());
+        }
+        self.select_dream(&command.dream_id());
+        self.status = Some(format!("{} {}", verb, command.describe()));
+        Ok(())
+    }
+
+    // The three primitive mutations below stage the change for the store but
+    // leave the history alone.
+
+    fn insert(&mut self, dream: Dream, index: usize) {
+        self.dreams.insert(index.min(self.dreams.len()), dream.clone());
+        self.pending.push(Change::Insert(dream));
+        self.dreams_changed();
+    }
+
+    /// Returns the dream as it was before.
+    fn replace(&mut self, dream: Dream) -> Option<Dream> {
+        let index = self.dreams.iter().position(|d| d.id == dream.id)?;
+        let before = std::mem::replace(&mut self.dreams[index], dream.clone());
+        self.pending.push(Change::Update(dream));
+        self.dreams_changed();
+        Some(before)
+    }
+
+    /// Returns the removed dream and where it was.
+    fn remove(&mut self, id: &Uuid) -> Option<(Dream, usize)> {
+        let index = self.dreams.iter().position(|d| d.id == *id)?;
+        let dream = self.dreams.remove(index);
+        self.pending.push(Change::Delete(*id));
+        self.dreams_changed();
+        Some((dream, index))
+    }
+
+    fn dreams_changed(&mut self) {
+        self.index = SearchIndex::new(&self.dreams, &self.config.date_format);
+        self.streaks = None;
+        self.refresh_view();
+    }
+
+    /// Recomputes which dreams are shown and keeps the selection in range.
+    pub fn refresh_view(&mut self) {
+        self.view = (0..self.dreams.len())
+            .filter(|&i| {
+                self.filter
+                    .as_ref()
+                    .is_none_or(|filter| filter.matches(&self.dreams[i], &self.config))
+            })
+            .collect();
+        let sort = self.state.sort;
+        self.view
+            .sort_by(|&a, &b| sort.compare(&self.dreams[a], &self.dreams[b]));
+        self.selected = self.selected.min(self.view.len().saturating_sub(1));
+        self.scroll_to_selected();
+        self.refresh_search_hits();
+    }
+
+    fn refresh_search_hits(&mut self) {
+        let matches = self.index.search(&self.search);
+        self.search_hits = self
+            .view
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| matches.binary_search(i).is_ok())
+            .map(|(position, _)| position)
+            .collect();
+    }
+
+    /// Re-runs the search after the query changed and selects the first hit
+    /// from the current dream on.
+    pub fn update_search(&mut self) {
+        self.refresh_search_hits();
+        if let Some(&hit) = self
+            .search_hits
+            .iter()
+            .find(|&&hit| hit >= self.selected)
+            .or(self.search_hits.first())
+        {
+            self.selected = hit;
+            self.scroll_to_selected();
+        }
+    }
+
+    /// Selects the next (or previous) search hit, wrapping around.
+    pub fn jump_to_hit(&mut self, forward: bool) {
+        let hit = if forward {
+            self.search_hits
+                .iter()
+                .find(|&&hit| hit > self.selected)
+                .or(self.search_hits.first())
+        } else {
+            self.search_hits
+                .iter()
+                .rev()
+                .find(|&&hit| hit < self.selected)
+                .or(self.search_hits.last())
+        };
+        if let Some(&hit) = hit {
+            self.selected = hit;
+            self.scroll_to_selected();
+        }
+    }
+
+    pub fn clear_search(&mut self) {
+        self.search.clear();
+        self.search_hits.clear();
+    }
+
+    pub fn selected_dream(&self) -> Option<&Dream> {
+        self.view.get(self.selected).map(|&i| &self.dreams[i])
+    }
+
+    /// Selects the dream with `id` if it is shown.
+    pub fn select_dream(&mut self, id: &Uuid) {
+        if let Some(position) = self.view.iter().position(|&i| self.dreams[i].id == *id) {
+            self.selected = position;
+            self.scroll_to_selected();
+        }
+    }
+
+    /// Moves `visible_start` just enough to keep the selected card on screen.
+    pub fn scroll_to_selected(&mut self) {
+        let max_track = self.config.max_track;
+        if self.selected < self.visible_start {
+            self.visible_start = self.selected;
+        } else if self.selected >= self.visible_start + max_track {
+            self.visible_start = self.selected + 1 - max_track;
+        }
+        self.visible_start = self
+            .visible_start
+            .min(self.view.len().saturating_sub(max_track));
+    }
+
+    /// Every tag in use with the number of dreams carrying it, most used first.
+    pub fn tag_counts(&self) -> Vec<(String, usize)> {
+        let mut counts = BTreeMap::new();
+        for tag in self.dreams.iter().flat_map(|d| &d.tags) {
+            *counts.entry(tag.clone()).or_insert(0) += 1;
+        }
+        let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// When the dream being edited happened, as chosen in the date step.
+    pub fn date_choice(&self) -> Result<DateTime<FixedOffset>, String> {
+        if self.date_input.trim().is_empty() {
+            return Ok(self.current_dream.date);
+        }
+        from_local(parse_when(
+            &self.date_input,
+            Local::now().naive_local(),
+            &self.config.date_format,
+        )?)
+    }
+
+    /// Moves the chosen date by `days` and `months`, keeping the time.
+    pub fn shift_date(&mut self, days: i64, months: i32) {
+        let date_format = &self.config.date_format;
+        let chosen = self
+            .date_choice()
+            .unwrap_or(self.current_dream.date)
+            .naive_local()
+            + Duration::days(days);
+        let shifted = if months < 0 {
+            chosen.checked_sub_months(Months::new(months.unsigned_abs()))
+        } else {
+            chosen.checked_add_months(Months::new(months as u32))
+        };
+        if let Some(shifted) = shifted {
+            self.date_input = shifted.format(date_format).to_string();
         }
     }
+
+    /// Number of dreams and the strongest intensity of each night.
+    pub fn nights(&self) -> BTreeMap<NaiveDate, (usize, Intensity)> {
+        let mut nights = BTreeMap::new();
+        for dream in &self.dreams {
+            let night = nights
+                .entry(dream.night(self.config.night_cutoff))
+                .or_insert((0, Intensity::Low));
+            night.0 += 1;
+            night.1 = night.1.clone().max(dream.intensity.clone());
+        }
+        nights
+    }
+
+    /// Statistics over the whole journal, with streaks and goals counted up
+    /// to tonight.
+    pub fn stats(&self) -> Stats {
+        Stats::new(&self.dreams, &self.config, self.tonight())
+    }
+
+    /// Streaks up to tonight, counted again only once the dreams or the
+    /// night change.
+    pub fn streaks(&mut self) -> &Streaks {
+        let tonight = self.tonight();
+        if self.streaks.as_ref().is_none_or(|(night, _)| *night != tonight) {
+            let streaks = Streaks::new(&self.nights().into_keys().collect(), tonight);
+            self.streaks = Some((tonight, streaks));
+        }
+        &self.streaks.as_ref().unwrap().1
+    }
+
+    /// The night in progress.
+    pub fn tonight(&self) -> NaiveDate {
+        night_of(Local::now().naive_local(), self.config.night_cutoff)
+    }
+
+    /// Existing tags that complete the last word of `tag_input` and are not
+    /// entered yet.
+    pub fn tag_suggestions(&self) -> Vec<String> {
+        let (entered, partial) = split_tag_input(&self.tag_input);
+        let Some(partial) = normalize_tag(partial) else {
+            return Vec::new();
+        };
+        let entered = parse_tags(entered);
+        self.tag_counts()
+            .into_iter()
+            .map(|(tag, _)| tag)
+            .filter(|tag| tag.starts_with(&partial) && *tag != partial && !entered.contains(tag))
+            .collect()
+    }
+
+    /// Replaces the last word of `tag_input` with the first suggestion.
+    pub fn complete_tag(&mut self) {
+        if let Some(tag) = self.tag_suggestions().into_iter().next() {
+            let (entered, _) = split_tag_input(&self.tag_input);
+            self.tag_input = format!("{}{} ", entered, tag);
+        }
+    }
+
+    /// Switches to the next sort mode, keeping the same dream selected, and
+    /// remembers it for the next session.
+    pub fn cycle_sort(&mut self) -> Result<(), Box<dyn Error>> {
+        let selected = self.selected_dream().map(|d| d.id);
+        self.state.sort = self.state.sort.next();
+        self.refresh_view();
+        if let Some(id) = selected {
+            self.select_dream(&id);
+        }
+        self.state.save()
+    }
+
+    pub fn set_filter(&mut self, filter: Option<Filter>) {
+        self.filter = filter;
+        self.selected = 0;
+        self.visible_start = 0;
+        self.refresh_view();
+    }
+
+    /// Finds a dream by its full id or a unique prefix of it.
+    pub fn find_dream(&self, id: &str) -> Result<&Dream, Box<dyn Error>> {
+        let id = id.to_ascii_lowercase();
+        let mut matches = self
+            .dreams
+            .iter()
+            .filter(|d| d.id.to_string().starts_with(&id));
+        match (matches.next(), matches.next()) {
+            (Some(dream), None) if !id.is_empty() => Ok(dream),
+            (Some(_), Some(_)) => Err(format!("id '{}' matches more than one dream", id).into()),
+            _ => Err(format!("no dream with id '{}'", id).into()),
+        }
+    }
+
+    fn backup(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.config.backups > 0 && self.store.path().exists() {
+            let dest = backup_path(self.store.path())?;
+            if let Err(err) = self.store.snapshot(&dest) {
+                let _ = std::fs::remove_file(&dest);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps every entry that still parses. The damaged file is copied aside
+    /// first, so the next save cannot destroy anything.
+    pub fn salvage(&mut self) -> Result<(), Box<dyn Error>> {
+        let copy = preserve_corrupt(self.store.path(), self.store.key())?;
+        let dreams = self.store.salvage()?;
+        self.status = Some(format!(
+            "Salvaged {} dreams; original kept at {}",
+            dreams.len(),
+            copy.display()
+        ));
+        self.replace_dreams(dreams);
+        self.history.mark_unsaved();
+        Ok(())
+    }
+
+    /// Restores `backup` over the journal. The current journal is backed up
+    /// first (or kept aside if it is damaged), so a restore can be undone.
+    pub fn restore_backup(&mut self, backup: &Path) -> Result<(), Box<dyn Error>> {
+        if self.load_error.is_some() {
+            preserve_corrupt(self.store.path(), self.store.key())?;
+        } else {
+            self.backup()?;
+        }
+        let dreams = self.store.restore(backup)?;
+        prune_backups(self.store.path(), self.config.backups)?;
+        self.status = Some(format!("Restored {}", backup.display()));
+        self.replace_dreams(dreams);
+        self.backups = list_backups(self.store.path());
+        if self.config.persist_history {
+            self.history.save(self.store.path(), self.store.key())?;
+        }
+        Ok(())
+    }
+
+    /// Writes the journal, its backups, the copies kept aside by upgrades and
+    /// recovery, and its undo history again, encrypted with `key` or in
+    /// plaintext for `None`. Returns how many backups and kept copies were
+    /// rewritten and the files that could not be read with the old key, which
+    /// are left as they are.
+    pub fn rekey(&mut self, key: Option<Key>) -> Result<(usize, usize, Vec<PathBuf>), Box<dyn Error>> {
+        let journal = self.store.path().to_path_buf();
+        // Read with the old key: plaintext is only accepted when there was none.
+        let mut backups = Vec::new();
+        let mut unreadable = Vec::new();
+        for backup in list_backups(&journal) {
+            let modified = std::fs::metadata(&backup)?.modified()?;
+            match crypt::open(&std::fs::read(&backup)?, self.store.key()) {
+                Ok(data) => backups.push((backup, data, modified)),
+                Err(_) => unreadable.push(backup),
+            }
+        }
+        let mut copies = Vec::new();
+        for copy in kept_copies(&journal) {
+            match crypt::open(&std::fs::read(&copy)?, self.store.key()) {
+                Ok(data) => copies.push((copy, data)),
+                Err(_) => unreadable.push(copy),
+            }
+        }
+        let mut history = None;
+        if history_path(&journal).exists() {
+            match History::load(&journal, self.store.key(), &self.config.date_format) {
+                Ok(loaded) => history = Some(loaded),
+                Err(_) => unreadable.push(history_path(&journal)),
+            }
+        }
+
+        self.store.set_key(key)?;
+        self.store.flush()?;
+        for (backup, data, modified) in &backups {
+            write_atomic(backup, &crypt::seal(data, self.store.key())?)?;
+            // Backups are ordered by modification time.
+            std::fs::File::options()
+                .write(true)
+                .open(backup)?
+                .set_modified(*modified)?;
+        }
+        for (copy, data) in &copies {
+            write_atomic(copy, &crypt::seal(data, self.store.key())?)?;
+        }
+        if let Some(history) = history {
+            history.save(&journal, self.store.key())?;
+        }
+        Ok((backups.len(), copies.len(), unreadable))
+    }
+
+    /// Swaps in a journal from outside the history, which no longer applies.
+    fn replace_dreams(&mut self, dreams: Vec<Dream>) {
+        self.dreams = dreams;
+        self.history = History::default();
+        self.pending.clear();
+        self.load_error = None;
+        self.selected = 0;
+        self.visible_start = 0;
+        self.input_mode = InputMode::Normal;
+        self.dreams_changed();
+    }
+}
+
+/// Splits tag input into the finished part and the word being typed.
+fn split_tag_input(input: &str) -> (&str, &str) {
+    let start = input
+        .rfind(|c: char| c == ',' || c.is_whitespace())
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    input.split_at(start)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{constants::DATE_FORMAT, store::JsonStore};
+
+    fn open_app(journal: &Path, key: Option<Key>) -> DreamApp {
+        let config = Config {
+            persist_history: true,
+            ..Config::default()
+        };
+        let mut store = JsonStore::new(journal.to_path_buf(), DATE_FORMAT);
+        store.set_key(key).unwrap();
+        DreamApp::new(Box::new(store), config, State::default())
+    }
+
+    /// Every file in `dir` containing `text`.
+    fn files_containing(dir: &Path, text: &str) -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                let data = std::fs::read(path).unwrap();
+                data.windows(text.len()).any(|window| window == text.as_bytes())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn encrypting_leaves_no_plaintext_beside_the_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = dir.path().join("journal.json");
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/journal-v2.json");
+        std::fs::copy(fixture, &journal).unwrap();
+        // Loading upgrades the journal and keeps the original aside.
+        let mut app = open_app(&journal, None);
+        let mut dream = app.dreams[0].clone();
+        dream.id = Uuid::new_v4();
+        dream.experience = "Swimming with a secret whale.".to_string();
+        app.add_dream(dream);
+        // Each save backs up the journal first, however quickly they follow.
+        app.save().unwrap();
+        app.save().unwrap();
+        preserve_corrupt(&journal, None).unwrap();
+        assert_eq!(list_backups(&journal).len(), 2);
+        assert_eq!(kept_copies(&journal).len(), 2);
+        assert!(history_path(&journal).exists());
+        assert_eq!(files_containing(dir.path(), "forest").len(), 5);
+
+        let (backups, copies, unreadable) = app.rekey(Some(Key::new("passphrase").unwrap())).unwrap();
+        assert_eq!((backups, copies), (2, 2));
+        assert!(unreadable.is_empty());
+        for text in ["forest", "secret whale"] {
+            assert!(files_containing(dir.path(), text).is_empty());
+        }
+
+        // A damaged journal found while encrypted is kept aside encrypted.
+        let encrypted = std::fs::read(&journal).unwrap();
+        std::fs::write(&journal, "{ \"dreams\": [ \"Chased through a forest.").unwrap();
+        let copy = preserve_corrupt(&journal, app.store.key()).unwrap();
+        assert!(crypt::is_encrypted(&std::fs::read(copy).unwrap()));
+        std::fs::write(&journal, &encrypted).unwrap();
+        assert!(files_containing(dir.path(), "forest").is_empty());
+
+        let key = Key::unlock(&encrypted, "passphrase").unwrap();
+        let mut app = open_app(&journal, Some(key));
+        assert_eq!(app.dreams.len(), 2);
+        let copies = kept_copies(&journal);
+        assert_eq!(copies.len(), 3);
+        assert_eq!(app.rekey(None).unwrap(), (2, 3, Vec::new()));
+        let original = copies.iter().find(|copy| copy.to_string_lossy().contains(".schema-v2-")).unwrap();
+        assert!(std::fs::read_to_string(original).unwrap().contains("\"schema_version\": 2"));
+    }
+
+    #[test]
+    fn rekey_leaves_files_it_cannot_read_as_they_are() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = dir.path().join("journal.json");
+        let mut app = open_app(&journal, None);
+        app.add_dream(Dream::new(Local::now().fixed_offset(), Intensity::Low, Style::Lucid));
+        for _ in 0..5 {
+            app.save().unwrap();
+        }
+        assert_eq!(app.rekey(Some(Key::new("first").unwrap())).unwrap(), (4, 0, Vec::new()));
+
+        // One backup under another passphrase, one cut short and one in
+        // plaintext, which an encrypted journal no longer accepts.
+        let backups = list_backups(&journal);
+        let plaintext = crypt::open(&std::fs::read(&backups[0]).unwrap(), app.store.key()).unwrap();
+        let other = Key::new("other").unwrap();
+        std::fs::write(&backups[0], crypt::seal(&plaintext, Some(&other)).unwrap()).unwrap();
+        let sealed = std::fs::read(&backups[1]).unwrap();
+        std::fs::write(&backups[1], &sealed[..sealed.len() / 2]).unwrap();
+        std::fs::write(&backups[2], &plaintext).unwrap();
+        let damaged: Vec<Vec<u8>> = backups[..3].iter().map(|backup| std::fs::read(backup).unwrap()).collect();
+
+        let (rewritten, copies, mut unreadable) = app.rekey(Some(Key::new("second").unwrap())).unwrap();
+        assert_eq!((rewritten, copies), (1, 0));
+        unreadable.sort();
+        let mut expected = backups[..3].to_vec();
+        expected.sort();
+        assert_eq!(unreadable, expected);
+        for (backup, data) in backups[..3].iter().zip(&damaged) {
+            assert_eq!(&std::fs::read(backup).unwrap(), data);
+        }
+
+        // The journal and the readable backup move to the new passphrase.
+        let encrypted = std::fs::read(&journal).unwrap();
+        assert!(Key::unlock(&encrypted, "first").is_err());
+        let key = Key::unlock(&encrypted, "second").unwrap();
+        assert!(crypt::open(&std::fs::read(&backups[3]).unwrap(), Some(&key)).is_ok());
+        assert_eq!(open_app(&journal, Some(key)).dreams.len(), 1);
+    }
+
+    #[test]
+    fn streaks_follow_added_and_removed_dreams() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = open_app(&dir.path().join("journal.json"), None);
+        assert_eq!(app.streaks().current, 0);
+
+        let dream = Dream::new(Local::now().fixed_offset(), Intensity::Low, Style::Lucid);
+        let id = dream.id;
+        app.add_dream(dream);
+        assert_eq!(app.streaks().current, 1);
+        app.delete_dream(&id);
+        assert_eq!(app.streaks().current, 0);
+        app.undo().unwrap();
+        assert_eq!(app.streaks().current, 1);
+    }
+
+    #[test]
+    fn search_follows_edits_and_deletes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = open_app(&dir.path().join("journal.json"), None);
+        for experience in ["A whale sang", "Lost keys", "Whales in the attic"] {
+            let mut dream = Dream::new(Local::now().fixed_offset(), Intensity::Low, Style::Lucid);
+            dream.experience = experience.to_string();
+            app.add_dream(dream);
+        }
+        let hits = |app: &DreamApp| -> Vec<String> {
+            let mut hits: Vec<String> = app
+                .search_hits
+                .iter()
+                .map(|&hit| app.dreams[app.view[hit]].experience.clone())
+                .collect();
+            hits.sort();
+            hits
+        };
+        app.search = "whale".to_string();
+        app.update_search();
+        assert_eq!(hits(&app), ["A whale sang", "Whales in the attic"]);
+
+        let mut edited = app.dreams[1].clone();
+        edited.experience = "Lost keys to a whale tank".to_string();
+        app.update_dream(edited);
+        assert_eq!(hits(&app), ["A whale sang", "Lost keys to a whale tank", "Whales in the attic"]);
+
+        let id = app.dreams[0].id;
+        app.delete_dream(&id);
+        assert_eq!(hits(&app), ["Lost keys to a whale tank", "Whales in the attic"]);
+    }
+}
@@ -0,0 +1,597 @@
+use crate::{
+    app::{DreamApp, SortMode},
+    config::{config_path, Config, DEFAULT_CONFIG},
+    crypt::Key,
+    dates::{from_local, parse_when},
+    filter::Filter,
+    dream::{normalize_tag, Dream, Intensity, Style},
+    csv_format,
+    html,
+    import::{self, ImportFormat, Outcome},
+    markdown, schema,
+    stats::Stats,
+    store::{write_atomic, StoreKind},
+};
+use chrono::{DateTime, FixedOffset, Local};
+use clap::{Parser, Subcommand};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyModifiers},
+    terminal::{disable_raw_mode, enable_raw_mode},
+};
+use std::{
+    error::Error,
+    io::{self, IsTerminal, Read},
+    path::PathBuf,
+};
+use zeroize::Zeroizing;
+
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Journal file [default: $XDG_DATA_HOME/dreaming/dreams_export.json]
+    #[arg(long, env = "DREAMING_JOURNAL", global = true)]
+    pub journal: Option<PathBuf>,
+
+    /// Storage backend for the journal [default: json]
+    #[arg(long, value_enum, global = true)]
+    pub store: Option<StoreKind>,
+
+    /// Number of timestamped backups to keep next to the journal [default: 5]
+    #[arg(long, global = true)]
+    pub backups: Option<usize>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Without a subcommand the TUI is opened.
+#[derive(Subcommand)]
+pub enum Command {
+    /// Record a new dream
+    Add {
+        /// When the dream happened, e.g. "yesterday 6am" or "2025-01-31 07:30" [default: now]
+        #[arg(long)]
+        date: Option<String>,
+        /// How vivid the dream was [default: from [defaults] in the config]
+        #[arg(long)]
+        intensity: Option<Intensity>,
+        /// Kind of dream [default: from [defaults] in the config]
+        #[arg(long)]
+        style: Option<Style>,
+        #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=10))]
+        frequency: u8,
+        /// Tag the dream; repeat for several tags
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// The experience; read from stdin when omitted
+        experience: Option<String>,
+    },
+    /// List every dream, one per line
+    List {
+        /// Only dreams with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Only dreams matching a filter such as "style:nightmare intensity>=medium freq>5"
+        #[arg(long, allow_hyphen_values = true)]
+        filter: Option<Filter>,
+        /// Order of the listing
+        #[arg(long, value_enum, default_value = "added")]
+        sort: SortMode,
+    },
+    /// Show a dream in full
+    Show {
+        /// Id as shown by `list`, or any unique prefix of it
+        id: String,
+    },
+    /// Change fields of an existing dream
+    Edit {
+        /// Id as shown by `list`, or any unique prefix of it
+        id: String,
+        /// When the dream happened, e.g. "yesterday 6am" or "2025-01-31 07:30"
+        #[arg(long)]
+        date: Option<String>,
+        #[arg(long)]
+        intensity: Option<Intensity>,
+        #[arg(long)]
+        style: Option<Style>,
+        #[arg(long, value_parser = clap::value_parser!(u8).range(0..=10))]
+        frequency: Option<u8>,
+        /// Add a tag; repeat for several tags
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Remove a tag; repeat for several tags
+        #[arg(long = "untag")]
+        untags: Vec<String>,
+        /// Replace the experience; use "-" to read it from stdin
+        #[arg(long)]
+        experience: Option<String>,
+    },
+    /// Delete a dream
+    Delete {
+        /// Id as shown by `list`, or any unique prefix of it
+        id: String,
+    },
+    /// Write the journal as JSON, Markdown, CSV or a static HTML site
+    Export {
+        /// Output file, or directory with --per-dream or --format html; stdout
+        /// when omitted
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        #[arg(long, value_enum, default_value = "json")]
+        format: ExportFormat,
+        /// Write one Markdown file per dream, with its fields in YAML front
+        /// matter, into the --output directory
+        #[arg(long, requires = "output")]
+        per_dream: bool,
+    },
+    /// Add dreams from Markdown with YAML front matter (as written by `export
+    /// --format markdown --per-dream`), CSV, dated text or JSON Lines files
+    Import {
+        /// Files to read, or directories whose .md, .markdown, .txt, .csv,
+        /// .jsonl and .ndjson files are read
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Format of the files [default: from the extension, or for other
+        /// files markdown if they start with front matter, else text]
+        #[arg(long, value_enum)]
+        format: Option<ImportFormat>,
+        /// Read a field from a CSV column or JSON key with another name, e.g.
+        /// --map experience=Notes; repeat for several fields
+        #[arg(long = "map", value_name = "FIELD=COLUMN")]
+        map: Vec<String>,
+        /// List what would be imported and which rows fail, without changing
+        /// the journal
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List every tag with the number of dreams carrying it
+    Tags,
+    /// Print summary statistics
+    Stats {
+        /// Print the statistics as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Encrypt the journal with a passphrase, or change the passphrase
+    Passwd {
+        /// Stop encrypting and write the journal in plaintext again
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Show where the configuration file is read from
+    Config {
+        /// Print an annotated configuration file with every default
+        #[arg(long)]
+        print_default: bool,
+    },
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    /// One document grouped by night
+    Markdown,
+    /// A row per dream with every field
+    Csv,
+    /// A static site, written into the --output directory
+    Html,
+}
+
+pub fn run(command: Command, mut app: DreamApp) -> Result<(), Box<dyn Error>> {
+    if let Some(err) = &app.load_error {
+        let position = err
+            .position
+            .map(|(line, column)| format!(" at line {}, column {}", line, column))
+            .unwrap_or_default();
+        return Err(format!(
+            "the journal failed to load{}: {}\nrun `dreaming` without arguments to recover it",
+            position, err.message
+        )
+        .into());
+    }
+
+    match command {
+        Command::Add {
+            date,
+            intensity,
+            style,
+            frequency,
+            tags,
+            experience,
+        } => {
+            let experience = match experience {
+                Some(text) => text,
+                None => read_stdin()?,
+            };
+            let date = match date {
+                Some(date) => parse_date(&date, &app.config.date_format)?,
+                None => chrono::Local::now().fixed_offset(),
+            };
+            let mut dream = Dream::new(
+                date,
+                intensity.unwrap_or_else(|| app.config.defaults.intensity.clone()),
+                style.unwrap_or_else(|| app.config.defaults.style.clone()),
+            );
+            dream.experience = non_empty(experience);
+            dream.frequency = frequency;
+            dream.tags = normalize_tags(&tags);
+            let id = dream.short_id();
+            app.add_dream(dream);
+            app.save()?;
+            println!("Added {}", id);
+        }
+        Command::List { tag, filter, sort } => {
+            let tag = tag.as_deref().and_then(normalize_tag);
+            let mut dreams: Vec<&Dream> = app
+                .dreams
+                .iter()
+                .filter(|dream| {
+                    tag.as_ref().is_none_or(|tag| dream.tags.contains(tag))
+                        && filter
+                            .as_ref()
+                            .is_none_or(|filter| filter.matches(dream, &app.config))
+                })
+                .collect();
+            dreams.sort_by(|a, b| sort.compare(a, b));
+            for dream in dreams {
+                println!("{}", summary_line(dream, &app.config.date_format));
+            }
+        }
+        Command::Show { id } => {
+            print!("{}", details(app.find_dream(&id)?, &app.config));
+        }
+        Command::Edit {
+            id,
+            date,
+            intensity,
+            style,
+            frequency,
+            tags,
+            untags,
+            experience,
+        } => {
+            let mut dream = app.find_dream(&id)?.clone();
+            if let Some(date) = date {
+                dream.date = parse_date(&date, &app.config.date_format)?;
+            }
+            if let Some(intensity) = intensity {
+                dream.intensity = intensity;
+            }
+            if let Some(style) = style {
+                dream.style = style;
+            }
+            if let Some(frequency) = frequency {
+                dream.frequency = frequency;
+            }
+            let untags = normalize_tags(&untags);
+            dream.tags.retain(|tag| !untags.contains(tag));
+            dream.tags.extend(normalize_tags(&tags));
+            dream.tags.sort();
+            dream.tags.dedup();
+            match experience.as_deref() {
+                Some("-") => dream.experience = non_empty(read_stdin()?),
+                Some(text) => dream.experience = non_empty(text.to_string()),
+                None => {}
+            }
+            let id = dream.short_id();
+            app.update_dream(dream);
+            app.save()?;
+            println!("Updated {}", id);
+        }
+        Command::Delete { id } => {
+            let dream = app.find_dream(&id)?;
+            let (id, short_id) = (dream.id, dream.short_id());
+            app.delete_dream(&id);
+            app.save()?;
+            println!("Deleted {}", short_id);
+        }
+        Command::Export {
+            output,
+            format,
+            per_dream,
+        } => {
+            if per_dream {
+                let (ExportFormat::Markdown, Some(dir)) = (format, output) else {
+                    return Err("--per-dream only applies to --format markdown".into());
+                };
+                std::fs::create_dir_all(&dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+                let cutoff = app.config.night_cutoff;
+                for dream in &app.dreams {
+                    let path = dir.join(markdown::file_name(dream, cutoff));
+                    write_atomic(&path, markdown::dream_file(dream, cutoff).as_bytes())?;
+                }
+                println!("Wrote {} files to {}", app.dreams.len(), dir.display());
+                return Ok(());
+            }
+            let serialized = match format {
+                ExportFormat::Json => schema::encode(&app.dreams)?,
+                ExportFormat::Markdown => markdown::journal_document(&app.dreams, &app.config),
+                ExportFormat::Csv => csv_format::export(&app.dreams, &app.config)?,
+                ExportFormat::Html => {
+                    let Some(dir) = output else {
+                        return Err("--format html writes a directory; name it with --output".into());
+                    };
+                    let pages = html::export(&app.dreams, &app.config, &dir)?;
+                    println!("Wrote {} pages to {}", pages, dir.display());
+                    return Ok(());
+                }
+            };
+            match output {
+                Some(path) => write_atomic(&path, serialized.as_bytes())?,
+                None => println!("{}", serialized.trim_end()),
+            }
+        }
+        Command::Import {
+            paths,
+            format,
+            map,
+            dry_run,
+        } => {
+            let mapping = import::parse_mapping(&map)?;
+            let candidates = import::prepare(&paths, format, &mapping, &app.dreams, &app.config)?;
+            let (new, duplicates, failed) = import::tally(&candidates);
+            for candidate in candidates {
+                match candidate.outcome {
+                    Outcome::New(dream) if dry_run => {
+                        println!("{}", summary_line(&dream, &app.config.date_format));
+                    }
+                    Outcome::New(dream) => app.import_dream(dream),
+                    Outcome::Duplicate(_, of) => {
+                        eprintln!("{}: already in the journal as {}", candidate.source, of);
+                    }
+                    Outcome::Failed(err) => eprintln!("{}: {}", candidate.source, err),
+                }
+            }
+            if dry_run {
+                println!(
+                    "Would import {} dreams; {} already in the journal, {} failed",
+                    new, duplicates, failed
+                );
+            } else {
+                if new > 0 {
+                    app.save()?;
+                }
+                println!(
+                    "Imported {} dreams; {} already in the journal, {} failed",
+                    new, duplicates, failed
+                );
+            }
+        }
+        Command::Tags => {
+            for (tag, count) in app.tag_counts() {
+                println!("{:>4}  {}", count, tag);
+            }
+        }
+        Command::Stats { json } => {
+            let stats = app.stats();
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else {
+                print_stats(&stats);
+            }
+        }
+        Command::Passwd { remove } => {
+            let key = if remove {
+                if app.store.key().is_none() {
+                    return Err("the journal is not encrypted".into());
+                }
+                None
+            } else {
+                let passphrase = read_passphrase("New passphrase: ")?;
+                if passphrase.is_empty() {
+                    return Err("the passphrase is empty; to stop encrypting use --remove".into());
+                }
+                if *read_passphrase("Repeat the new passphrase: ")? != *passphrase {
+                    return Err("the passphrases differ".into());
+                }
+                Some(Key::new(&passphrase)?)
+            };
+            let encrypted = key.is_some();
+            let (backups, copies, unreadable) = app.rekey(key)?;
+            for path in &unreadable {
+                eprintln!("{}: encrypted with another passphrase; left as it is", path.display());
+            }
+            println!(
+                "{} the journal, {} backup{} and {} kept cop{}",
+                if encrypted { "Encrypted" } else { "Decrypted" },
+                backups,
+                if backups == 1 { "" } else { "s" },
+                copies,
+                if copies == 1 { "y" } else { "ies" }
+            );
+        }
+        Command::Config { print_default } => run_config(print_default)?,
+    }
+
+    Ok(())
+}
+
+/// Handled before the configuration is loaded, so it still works when the
+/// current file is invalid.
+pub fn run_config(print_default: bool) -> Result<(), Box<dyn Error>> {
+    if print_default {
+        print!("{}", DEFAULT_CONFIG);
+        return Ok(());
+    }
+    match config_path() {
+        Some(path) if path.exists() => println!("{}", path.display()),
+        Some(path) => println!("{} (not created yet)", path.display()),
+        None => return Err("no configuration directory on this platform".into()),
+    }
+    Ok(())
+}
+
+/// The key of an encrypted journal, from `DREAMING_PASSPHRASE` or asked for
+/// on the terminal up to three times.
+pub fn unlock(data: &[u8]) -> Result<Key, Box<dyn Error>> {
+    if let Ok(passphrase) = std::env::var("DREAMING_PASSPHRASE") {
+        return Key::unlock(data, &passphrase);
+    }
+    if !io::stderr().is_terminal() {
+        return Err("the journal is encrypted; set DREAMING_PASSPHRASE or run in a terminal".into());
+    }
+    let mut attempts = 1;
+    loop {
+        match Key::unlock(data, &read_passphrase("Passphrase: ")?) {
+            Err(err) if attempts < 3 => eprintln!("{}", err),
+            result => return result,
+        }
+        attempts += 1;
+    }
+}
+
+/// Reads a line from the terminal without echoing it.
+fn read_passphrase(prompt: &str) -> Result<Zeroizing<String>, Box<dyn Error>> {
+    if !io::stderr().is_terminal() {
+        return Err("a passphrase can only be entered in a terminal".into());
+    }
+    eprint!("{}", prompt);
+    enable_raw_mode()?;
+    let mut passphrase = Zeroizing::new(String::new());
+    let result = loop {
+        let key = match event::read() {
+            Ok(Event::Key(key)) => key,
+            Ok(_) => continue,
+            Err(err) => break Err(err.into()),
+        };
+        match key.code {
+            KeyCode::Enter => break Ok(()),
+            KeyCode::Esc => break Err("cancelled".into()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                break Err("cancelled".into())
+            }
+            KeyCode::Backspace => {
+                passphrase.pop();
+            }
+            KeyCode::Char(c) => passphrase.push(c),
+            _ => {}
+        }
+    };
+    disable_raw_mode()?;
+    eprintln!();
+    result.map(|()| passphrase)
+}
+
+fn read_stdin() -> Result<String, Box<dyn Error>> {
+    if io::stdin().is_terminal() {
+        eprintln!("Describe the experience, then press Ctrl-D:");
+    }
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text)?;
+    Ok(text.trim_end().to_string())
+}
+
+/// Reads `--date` as local time.
+fn parse_date(input: &str, date_format: &str) -> Result<DateTime<FixedOffset>, Box<dyn Error>> {
+    let date = parse_when(input, Local::now().naive_local(), date_format)?;
+    Ok(from_local(date)?)
+}
+
+fn non_empty(text: String) -> String {
+    if text.trim().is_empty() {
+        "N/A".to_string()
+    } else {
+        text
+    }
+}
+
+/// Each argument is one tag, so `--tag "old house"` becomes `old-house`.
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut tags: Vec<String> = tags.iter().filter_map(|tag| normalize_tag(tag)).collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+fn summary_line(dream: &Dream, date_format: &str) -> String {
+    let first_line = dream.experience.lines().next().unwrap_or_default();
+    let excerpt: String = first_line.chars().take(50).collect();
+    format!(
+        "{}  {}  {:<6}  {:<9}  {:>2}  {}",
+        dream.short_id(),
+        dream.shown_date(date_format),
+        dream.intensity.to_string(),
+        dream.style.to_string(),
+        dream.frequency,
+        excerpt
+    )
+}
+
+fn details(dream: &Dream, config: &Config) -> String {
+    format!(
+        "Id: {}\nDate: {}\nNight of: {}\nIntensity: {}\nFrequency: {}\nStyle: {}\nTags: {}\nCreated: {}\nUpdated: {}\nExperience:\n{}\n",
+        dream.id,
+        dream.shown_date(&config.date_format),
+        dream.night(config.night_cutoff).format("%a %Y-%m-%d"),
+        dream.intensity,
+        dream.frequency,
+        dream.style,
+        dream.tags.join(", "),
+        dream.created_at.with_timezone(&Local).format(&config.date_format),
+        dream.updated_at.with_timezone(&Local).format(&config.date_format),
+        dream.experience
+    )
+}
+
+fn print_stats(stats: &Stats) {
+    println!("Dreams: {}", stats.dreams);
+    if stats.dreams == 0 {
+        return;
+    }
+
+    println!("Nights: {}", stats.nights);
+    if let Some(average) = stats.average_frequency {
+        println!("Average frequency: {:.1}", average);
+    }
+
+    let streaks = &stats.streaks;
+    print!("Streak: {} nights, longest {}", streaks.current, streaks.longest);
+    if let (Some(start), Some(end)) = (streaks.longest_start, streaks.longest_end) {
+        print!(" ({} to {})", start, end);
+    }
+    println!();
+
+    println!("Intensity:");
+    for intensity in &stats.intensities {
+        println!("  {:<10} {}", intensity.name, intensity.count);
+    }
+
+    println!("Style:");
+    for style in &stats.styles {
+        println!("  {:<10} {}", style.name, style.count);
+    }
+
+    println!("Dreams per week:");
+    for week in &stats.weeks {
+        match week.average_frequency {
+            Some(average) => println!("  {}  {:>3}  avg frequency {:.1}", week.start, week.dreams, average),
+            None => println!("  {}  {:>3}", week.start, week.dreams),
+        }
+    }
+
+    if !stats.goals.is_empty() {
+        println!("Goals:");
+        for progress in &stats.goals {
+            let filled = (progress.done * 10 / progress.target).min(10);
+            println!(
+                "  [{}{}] {}/{} this {}  {}",
+                "#".repeat(filled),
+                ".".repeat(10 - filled),
+                progress.done,
+                progress.target,
+                progress.per,
+                progress.goal
+            );
+        }
+    }
+
+    for (title, counts) in [("Top tags", &stats.top_tags), ("Top words", &stats.top_words)] {
+        if !counts.is_empty() {
+            let listed: Vec<String> = counts
+                .iter()
+                .map(|count| format!("{} ({})", count.name, count.count))
+                .collect();
+            println!("{}: {}", title, listed.join(", "));
+        }
+    }
+}
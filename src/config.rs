@@ -0,0 +1,443 @@
+use crate::{
+    constants::{BACKUP_COUNT, CONFIG_FILE, DATE_FORMAT, MAX_TRACK, NIGHT_CUTOFF_HOUR, TICK_RATE_DURATION},
+    dream::{Intensity, Style},
+    filter::Filter,
+    paths::config_dir,
+    store::StoreKind,
+};
+use chrono::{
+    format::{Item, StrftimeItems},
+    NaiveTime,
+};
+use ratatui::style::Color as TuiColor;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Settings read from `config.toml`. Anything left out keeps its default;
+/// command-line flags and environment variables take precedence.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub journal: Option<PathBuf>,
+    pub store: StoreKind,
+    pub backups: usize,
+    pub persist_history: bool,
+    pub tick_rate_ms: u64,
+    pub max_track: usize,
+    pub date_format: String,
+    #[serde(deserialize_with = "time_of_day")]
+    pub night_cutoff: NaiveTime,
+    pub defaults: Defaults,
+    pub colors: Theme,
+    pub goals: Vec<Goal>,
+}
+
+/// Initial values for a newly added dream.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Defaults {
+    #[serde(deserialize_with = "from_str")]
+    pub intensity: Intensity,
+    #[serde(deserialize_with = "from_str")]
+    pub style: Style,
+}
+
+/// A target such as five nights a week, counted over the current week or
+/// month.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Goal {
+    pub target: usize,
+    #[serde(default)]
+    pub count: GoalCount,
+    pub per: Period,
+    /// Only dreams matching this filter count.
+    #[serde(default, deserialize_with = "some_from_str")]
+    pub filter: Option<Filter>,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GoalCount {
+    /// Nights with at least one dream.
+    #[default]
+    Nights,
+    Dreams,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    /// Monday to Sunday.
+    Week,
+    Month,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub background: Color,
+    pub shadow: Color,
+    pub panel: Color,
+    pub panel_text: Color,
+    pub title: Color,
+    pub text: Color,
+    pub muted: Color,
+    pub status: Color,
+    pub saved: Color,
+    pub unsaved: Color,
+    pub highlight: Color,
+    pub low: Color,
+    pub medium: Color,
+    pub high: Color,
+}
+
+/// A terminal color written as a name (`"cyan"`) or as `"#rrggbb"`.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(try_from = "String")]
+pub struct Color(pub TuiColor);
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            journal: None,
+            store: StoreKind::Json,
+            backups: BACKUP_COUNT,
+            persist_history: false,
+            tick_rate_ms: TICK_RATE_DURATION,
+            max_track: MAX_TRACK,
+            date_format: DATE_FORMAT.to_string(),
+            night_cutoff: NaiveTime::from_hms_opt(NIGHT_CUTOFF_HOUR, 0, 0).unwrap(),
+            defaults: Defaults::default(),
+            colors: Theme::default(),
+            goals: Vec::new(),
+        }
+    }
+}
+
+impl Default for Defaults {
+    fn default() -> Defaults {
+        Defaults {
+            intensity: Intensity::Low,
+            style: Style::Lucid,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            background: Color(TuiColor::Rgb(0, 0, 50)),
+            shadow: Color(TuiColor::Rgb(0, 0, 40)),
+            panel: Color(TuiColor::Rgb(100, 216, 230)),
+            panel_text: Color(TuiColor::Black),
+            title: Color(TuiColor::Cyan),
+            text: Color(TuiColor::Gray),
+            muted: Color(TuiColor::DarkGray),
+            status: Color(TuiColor::Yellow),
+            saved: Color(TuiColor::Green),
+            unsaved: Color(TuiColor::Red),
+            highlight: Color(TuiColor::LightMagenta),
+            low: Color(TuiColor::Green),
+            medium: Color(TuiColor::Yellow),
+            high: Color(TuiColor::Red),
+        }
+    }
+}
+
+impl Theme {
+    pub fn intensity(&self, intensity: &Intensity) -> TuiColor {
+        match intensity {
+            Intensity::Low => self.low.0,
+            Intensity::Medium => self.medium.0,
+            Intensity::High => self.high.0,
+        }
+    }
+}
+
+impl Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match (self.count, self.target) {
+            (GoalCount::Nights, 1) => "night",
+            (GoalCount::Nights, _) => "nights",
+            (GoalCount::Dreams, 1) => "dream",
+            (GoalCount::Dreams, _) => "dreams",
+        };
+        write!(f, "{} {} a {}", self.target, unit, self.per)?;
+        if let Some(filter) = &self.filter {
+            write!(f, " ({})", filter)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Period::Week => write!(f, "week"),
+            Period::Month => write!(f, "month"),
+        }
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if let Some(hex) = value.strip_prefix('#') {
+            let channel = |range: std::ops::Range<usize>| {
+                hex.get(range)
+                    .and_then(|c| u8::from_str_radix(c, 16).ok())
+            };
+            return match (hex.len(), channel(0..2), channel(2..4), channel(4..6)) {
+                (6, Some(r), Some(g), Some(b)) => Ok(Color(TuiColor::Rgb(r, g, b))),
+                _ => Err(format!("invalid color '{}', expected #rrggbb", value)),
+            };
+        }
+
+        let color = match value.to_ascii_lowercase().as_str() {
+            "black" => TuiColor::Black,
+            "red" => TuiColor::Red,
+            "green" => TuiColor::Green,
+            "yellow" => TuiColor::Yellow,
+            "blue" => TuiColor::Blue,
+            "magenta" => TuiColor::Magenta,
+            "cyan" => TuiColor::Cyan,
+            "gray" => TuiColor::Gray,
+            "darkgray" => TuiColor::DarkGray,
+            "lightred" => TuiColor::LightRed,
+            "lightgreen" => TuiColor::LightGreen,
+            "lightyellow" => TuiColor::LightYellow,
+            "lightblue" => TuiColor::LightBlue,
+            "lightmagenta" => TuiColor::LightMagenta,
+            "lightcyan" => TuiColor::LightCyan,
+            "white" => TuiColor::White,
+            _ => return Err(format!("unknown color '{}'", value)),
+        };
+        Ok(Color(color))
+    }
+}
+
+/// Deserializes through `FromStr`, so enum values are case-insensitive.
+fn from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(de::Error::custom)
+}
+
+fn some_from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    from_str(deserializer).map(Some)
+}
+
+/// Reads a time of day written as `"HH:MM"`.
+fn time_of_day<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(&value, "%H:%M")
+        .map_err(|_| de::Error::custom(format!("invalid time '{}', expected HH:MM", value)))
+}
+
+impl Config {
+    pub fn load() -> Result<Config, Box<dyn Error>> {
+        match config_path() {
+            Some(path) => Config::read(&path),
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// Reads the config at `path`, with the defaults if there is none there.
+    fn read(path: &Path) -> Result<Config, Box<dyn Error>> {
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Config::default())
+            }
+            Err(err) => return Err(format!("{}: {}", path.display(), err).into()),
+        };
+        let config: Config =
+            toml::from_str(&data).map_err(|err| format!("{}: {}", path.display(), err))?;
+        config
+            .validate()
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(10..=5000).contains(&self.tick_rate_ms) {
+            return Err(format!(
+                "tick_rate_ms must be between 10 and 5000, got {}",
+                self.tick_rate_ms
+            ));
+        }
+        if !(1..=10).contains(&self.max_track) {
+            return Err(format!(
+                "max_track must be between 1 and 10, got {}",
+                self.max_track
+            ));
+        }
+        if self.date_format.is_empty()
+            || StrftimeItems::new(&self.date_format).any(|item| matches!(item, Item::Error))
+        {
+            return Err(format!("date_format '{}' is not a valid strftime format", self.date_format));
+        }
+        if let Some(goal) = self.goals.iter().find(|goal| goal.target == 0) {
+            return Err(format!("goal '{}' needs a target of at least 1", goal));
+        }
+        Ok(())
+    }
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(CONFIG_FILE))
+}
+
+/// The annotated template printed by `dreaming config --print-default`.
+pub const DEFAULT_CONFIG: &str = r##"# Dreaming configuration.
+# Save as $XDG_CONFIG_HOME/dreaming/config.toml (usually ~/.config/dreaming/config.toml).
+# Every setting is optional; remove a line to keep its default.
+
+# Journal file. Defaults to $XDG_DATA_HOME/dreaming/dreams_export.json
+# (or dreams.db for the SQLite backend). Overridden by --journal and DREAMING_JOURNAL.
+# journal = "~/Documents/dreams.json"
+
+# Storage backend: "json" or "sqlite".
+store = "json"
+
+# Number of timestamped backups kept next to the journal. 0 disables backups.
+backups = 5
+
+# Keep the undo history in <journal>.history.json so it survives restarts.
+persist_history = false
+
+# How often the screen refreshes, in milliseconds (10-5000).
+tick_rate_ms = 250
+
+# Number of record cards shown side by side (1-10).
+max_track = 4
+
+# strftime format dates are shown in, in local time. Dates are stored with
+# their UTC offset, so changing this or the time zone never alters them.
+date_format = "%Y-%m-%d %H:%M:%S"
+
+# Dreams before this time of day belong to the previous night, so a 2am
+# dream is grouped with the evening before it (HH:MM).
+night_cutoff = "12:00"
+
+# Starting values when adding a dream.
+[defaults]
+intensity = "low"   # low, medium, high
+style = "lucid"     # lucid, nightmare, recurring, prophetic, normal
+
+# Colors are names (black, red, green, yellow, blue, magenta, cyan, gray,
+# darkgray, white, lightred, lightgreen, lightyellow, lightblue,
+# lightmagenta, lightcyan) or "#rrggbb".
+[colors]
+background = "#000032"
+shadow = "#000028"
+panel = "#64d8e6"
+panel_text = "black"
+title = "cyan"
+text = "gray"
+muted = "darkgray"
+status = "yellow"
+saved = "green"
+unsaved = "red"
+highlight = "lightmagenta"   # search matches
+low = "green"
+medium = "yellow"
+high = "red"
+
+# Goals shown with progress bars on the statistics screen ('S') and by
+# `dreaming stats`. Each counts nights with a dream (count = "nights", the
+# default) or dreams (count = "dreams") in the current week or month, and can
+# be limited to dreams matching a filter, written as in `dreaming list --filter`.
+# [[goals]]
+# target = 5
+# per = "week"         # week, month
+#
+# [[goals]]
+# target = 3
+# count = "dreams"
+# per = "month"
+# filter = "style:lucid"
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The error from reading `text` as the config file, with the file's
+    /// directory shown as `<dir>`.
+    fn error(text: &str) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, text).unwrap();
+        let err = Config::read(&path).err().unwrap().to_string();
+        err.replace(&dir.path().display().to_string(), "<dir>")
+    }
+
+    #[test]
+    fn reads_a_missing_or_partial_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::read(&dir.path().join(CONFIG_FILE)).unwrap();
+        assert_eq!(config.tick_rate_ms, Config::default().tick_rate_ms);
+
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, "max_track = 2\n[defaults]\nstyle = \"Nightmare\"\n").unwrap();
+        let config = Config::read(&path).unwrap();
+        assert_eq!(config.max_track, 2);
+        assert!(config.defaults.style == Style::Nightmare);
+        assert!(config.defaults.intensity == Intensity::Low);
+    }
+
+    #[test]
+    fn names_the_file_line_and_reason_of_a_parse_error() {
+        let err = error("backups = 5\nmax_trak = 3\n");
+        assert!(err.starts_with("<dir>/config.toml: TOML parse error at line 2, column 1"), "{}", err);
+        assert!(err.contains("unknown field `max_trak`"), "{}", err);
+
+        let err = error("[defaults]\nintensity = \"loud\"\n");
+        assert!(err.starts_with("<dir>/config.toml: TOML parse error at line 2, column 13"), "{}", err);
+        assert!(err.ends_with("unknown intensity 'loud'\n"), "{}", err);
+
+        let err = error("night_cutoff = \"25:00\"\n");
+        assert!(err.starts_with("<dir>/config.toml: TOML parse error at line 1"), "{}", err);
+        assert!(err.ends_with("invalid time '25:00', expected HH:MM\n"), "{}", err);
+    }
+
+    #[test]
+    fn names_the_file_and_reason_of_an_invalid_value() {
+        assert_eq!(
+            error("tick_rate_ms = 5\n"),
+            "<dir>/config.toml: tick_rate_ms must be between 10 and 5000, got 5"
+        );
+        assert_eq!(
+            error("max_track = 11\n"),
+            "<dir>/config.toml: max_track must be between 1 and 10, got 11"
+        );
+        assert_eq!(
+            error("date_format = \"%Q\"\n"),
+            "<dir>/config.toml: date_format '%Q' is not a valid strftime format"
+        );
+        assert_eq!(
+            error("[[goals]]\ntarget = 0\nper = \"week\"\n"),
+            "<dir>/config.toml: goal '0 nights a week' needs a target of at least 1"
+        );
+    }
+}
@@ -1,3 +1,11 @@
 pub const DREAM_FILE: &str = "dreams_export.json";
+pub const DREAM_DB: &str = "dreams.db";
+pub const APP_DIR: &str = "dreaming";
+pub const CONFIG_FILE: &str = "config.toml";
+pub const STATE_FILE: &str = "state.toml";
+pub const BACKUP_COUNT: usize = 5;
+pub const HISTORY_LIMIT: usize = 200;
+pub const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+pub const NIGHT_CUTOFF_HOUR: u32 = 12;
 pub const TICK_RATE_DURATION: u64 = 250;
 pub const MAX_TRACK : usize = 4;
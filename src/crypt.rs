@@ -0,0 +1,262 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use std::{error::Error, path::Path};
+use zeroize::Zeroizing;
+
+/// First bytes of an encrypted file.
+const MAGIC: &[u8] = b"DREAMENC";
+const FORMAT_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+/// Magic, version, the three Argon2 costs, salt and nonce.
+const HEADER_LEN: usize = MAGIC.len() + 1 + 12 + SALT_LEN + NONCE_LEN;
+/// Largest memory (KiB), passes and lanes accepted from a header, so that a
+/// crafted file cannot make unlocking exhaust memory or time.
+const MAX_COSTS: [u32; 3] = [1024 * 1024, 64, 16];
+
+/// A key derived from a passphrase with Argon2id. Files are encrypted with
+/// XChaCha20-Poly1305 under a header holding the KDF parameters, the salt and
+/// a fresh nonce; the header is authenticated along with the contents.
+pub struct Key {
+    costs: [u32; 3],
+    salt: [u8; SALT_LEN],
+    key: Zeroizing<[u8; 32]>,
+}
+
+/// What the header of an encrypted file says.
+struct Header<'a> {
+    costs: [u32; 3],
+    salt: [u8; SALT_LEN],
+    nonce: &'a [u8],
+    /// Everything before the ciphertext, authenticated with it.
+    bytes: &'a [u8],
+    ciphertext: &'a [u8],
+}
+
+impl Key {
+    /// A key for newly encrypted files, with a fresh salt and the default
+    /// Argon2id costs.
+    pub fn new(passphrase: &str) -> Result<Key, Box<dyn Error>> {
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let params = Params::default();
+        Key::derive(passphrase, [params.m_cost(), params.t_cost(), params.p_cost()], salt)
+    }
+
+    /// The key `data` was encrypted with, if `passphrase` is right.
+    pub fn unlock(data: &[u8], passphrase: &str) -> Result<Key, Box<dyn Error>> {
+        let header = parse(data)?;
+        let key = Key::derive(passphrase, header.costs, header.salt)?;
+        key.decrypt(data)?;
+        Ok(key)
+    }
+
+    fn derive(passphrase: &str, costs: [u32; 3], salt: [u8; SALT_LEN]) -> Result<Key, Box<dyn Error>> {
+        let [m_cost, t_cost, p_cost] = costs;
+        let params = Params::new(m_cost, t_cost, p_cost, Some(32)).map_err(|err| err.to_string())?;
+        let mut key = Zeroizing::new([0; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+            .map_err(|err| err.to_string())?;
+        Ok(Key { costs, salt, key })
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut data = MAGIC.to_vec();
+        data.push(FORMAT_VERSION);
+        for cost in self.costs {
+            data.extend(cost.to_le_bytes());
+        }
+        data.extend(self.salt);
+        data.extend(nonce);
+        let ciphertext = self
+            .cipher()
+            .encrypt(&nonce, Payload { msg: plaintext, aad: &data })
+            .map_err(|_| "encryption failed")?;
+        data.extend(ciphertext);
+        Ok(data)
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let header = parse(data)?;
+        if header.costs != self.costs || header.salt != self.salt {
+            return Err("the file is encrypted with another passphrase".into());
+        }
+        let payload = Payload {
+            msg: header.ciphertext,
+            aad: header.bytes,
+        };
+        Ok(self
+            .cipher()
+            .decrypt(XNonce::from_slice(header.nonce), payload)
+            .map_err(|_| "wrong passphrase, or the file is damaged")?)
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(self.key.as_ref().into())
+    }
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// The contents of the file at `path` if it exists and is encrypted.
+pub fn read_encrypted(path: &Path) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    match std::fs::read(path) {
+        Ok(data) => Ok(is_encrypted(&data).then_some(data)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// The contents of a file as read from disk: decrypted with `key`, or as they
+/// are without one. With a key, a file in plaintext is refused, since it may
+/// have been put there in place of the encrypted one.
+pub fn open(data: &[u8], key: Option<&Key>) -> Result<Vec<u8>, Box<dyn Error>> {
+    match (key, is_encrypted(data)) {
+        (Some(key), true) => key.decrypt(data),
+        (Some(_), false) => Err("the file is not encrypted, though the journal is".into()),
+        (None, true) => Err("the file is encrypted and no passphrase was given".into()),
+        (None, false) => Ok(data.to_vec()),
+    }
+}
+
+/// The contents to write to disk: encrypted when there is a key.
+pub fn seal(plaintext: &[u8], key: Option<&Key>) -> Result<Vec<u8>, Box<dyn Error>> {
+    match key {
+        Some(key) => key.encrypt(plaintext),
+        None => Ok(plaintext.to_vec()),
+    }
+}
+
+/// A copy of a file's contents to write aside: encrypted when there is a key,
+/// and as it is if it is encrypted already.
+pub fn seal_copy(data: &[u8], key: Option<&Key>) -> Result<Vec<u8>, Box<dyn Error>> {
+    if is_encrypted(data) {
+        Ok(data.to_vec())
+    } else {
+        seal(data, key)
+    }
+}
+
+fn parse(data: &[u8]) -> Result<Header<'_>, Box<dyn Error>> {
+    if !is_encrypted(data) {
+        return Err("the file is not encrypted".into());
+    }
+    if data.len() < HEADER_LEN {
+        return Err("the encrypted file is truncated".into());
+    }
+    let version = data[MAGIC.len()];
+    if version != FORMAT_VERSION {
+        return Err(format!(
+            "encryption format version {} is not supported by this build",
+            version
+        )
+        .into());
+    }
+    let (bytes, ciphertext) = data.split_at(HEADER_LEN);
+    let mut at = MAGIC.len() + 1;
+    let mut costs = [0; 3];
+    for (cost, max) in costs.iter_mut().zip(MAX_COSTS) {
+        *cost = u32::from_le_bytes(bytes[at..at + 4].try_into()?);
+        if *cost > max {
+            return Err("the encrypted file asks for more work to unlock than this build allows".into());
+        }
+        at += 4;
+    }
+    let salt = bytes[at..at + SALT_LEN].try_into()?;
+    Ok(Header {
+        costs,
+        salt,
+        nonce: &bytes[at + SALT_LEN..],
+        bytes,
+        ciphertext,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A key with the lowest costs, so the tests run quickly.
+    fn key(passphrase: &str) -> Key {
+        Key::derive(passphrase, [8, 1, 1], [7; SALT_LEN]).unwrap()
+    }
+
+    fn error(result: Result<impl Sized, Box<dyn Error>>) -> String {
+        result.err().unwrap().to_string()
+    }
+
+    #[test]
+    fn round_trips_with_the_right_passphrase() {
+        let data = key("passphrase").encrypt(b"Flying over the harbour.").unwrap();
+        assert!(is_encrypted(&data));
+        assert!(!data.windows(6).any(|window| window == b"Flying"));
+        assert_ne!(data, key("passphrase").encrypt(b"Flying over the harbour.").unwrap());
+
+        let unlocked = Key::unlock(&data, "passphrase").unwrap();
+        assert_eq!(unlocked.decrypt(&data).unwrap(), b"Flying over the harbour.");
+        assert_eq!(open(&data, Some(&unlocked)).unwrap(), b"Flying over the harbour.");
+    }
+
+    #[test]
+    fn rejects_a_wrong_passphrase() {
+        let data = key("passphrase").encrypt(b"secret").unwrap();
+        assert_eq!(error(Key::unlock(&data, "Passphrase")), "wrong passphrase, or the file is damaged");
+        let other = Key::derive("passphrase", [8, 1, 1], [8; SALT_LEN]).unwrap();
+        assert_eq!(error(other.decrypt(&data)), "the file is encrypted with another passphrase");
+    }
+
+    #[test]
+    fn rejects_damaged_files() {
+        let data = key("passphrase").encrypt(b"secret").unwrap();
+
+        assert_eq!(error(key("passphrase").decrypt(&data[..HEADER_LEN - 1])), "the encrypted file is truncated");
+        assert_eq!(error(key("passphrase").decrypt(b"not encrypted")), "the file is not encrypted");
+
+        let mut newer = data.clone();
+        newer[MAGIC.len()] = 2;
+        assert_eq!(
+            error(key("passphrase").decrypt(&newer)),
+            "encryption format version 2 is not supported by this build"
+        );
+
+        let mut tampered = data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(error(key("passphrase").decrypt(&tampered)), "wrong passphrase, or the file is damaged");
+
+        // The nonce is part of the authenticated header.
+        let mut tampered = data.clone();
+        tampered[HEADER_LEN - 1] ^= 1;
+        assert_eq!(error(key("passphrase").decrypt(&tampered)), "wrong passphrase, or the file is damaged");
+    }
+
+    #[test]
+    fn caps_the_costs_read_from_a_header() {
+        let mut data = key("passphrase").encrypt(b"secret").unwrap();
+        let memory = MAGIC.len() + 1;
+        data[memory..memory + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            error(Key::unlock(&data, "passphrase")),
+            "the encrypted file asks for more work to unlock than this build allows"
+        );
+    }
+
+    #[test]
+    fn refuses_plaintext_once_encrypted() {
+        let key = key("passphrase");
+        assert_eq!(open(b"[]", None).unwrap(), b"[]");
+        assert_eq!(error(open(b"[]", Some(&key))), "the file is not encrypted, though the journal is");
+        let data = key.encrypt(b"[]").unwrap();
+        assert_eq!(error(open(&data, None)), "the file is encrypted and no passphrase was given");
+
+        assert_eq!(seal_copy(&data, Some(&key)).unwrap(), data);
+        assert!(is_encrypted(&seal_copy(b"[]", Some(&key)).unwrap()));
+        assert_eq!(seal_copy(b"[]", None).unwrap(), b"[]");
+    }
+}
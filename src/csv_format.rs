@@ -0,0 +1,226 @@
+use crate::{
+    config::Config,
+    dream::Dream,
+    import::{Importer, Parsed, Record, FIELDS},
+};
+use chrono::SecondsFormat;
+use std::{collections::BTreeMap, error::Error, path::Path};
+
+/// Columns of an export: every field, plus `night` for analysis, which is
+/// ignored on import.
+const COLUMNS: &[&str] = &[
+    "id",
+    "date",
+    "night",
+    "intensity",
+    "style",
+    "frequency",
+    "tags",
+    "experience",
+    "created_at",
+    "updated_at",
+];
+
+/// Every dream as a CSV row under a header. Tags are separated by spaces and
+/// multi-line experiences are quoted.
+pub fn export(dreams: &[Dream], config: &Config) -> Result<String, Box<dyn Error>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(COLUMNS)?;
+    for dream in dreams {
+        writer.write_record([
+            dream.id.to_string(),
+            dream.date.to_rfc3339_opts(SecondsFormat::AutoSi, false),
+            dream.night(config.night_cutoff).format("%Y-%m-%d").to_string(),
+            dream.intensity.to_string(),
+            dream.style.to_string(),
+            dream.frequency.to_string(),
+            dream.tags.join(" "),
+            dream.experience.clone(),
+            dream.created_at.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            dream.updated_at.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        ])?;
+    }
+    let data = writer.into_inner().map_err(|err| err.to_string())?;
+    Ok(String::from_utf8(data)?)
+}
+
+/// Reads a row per dream under a header. Each field is read from the column
+/// `mapping` names, or else from the column named like the field, ignoring
+/// case. Rows are numbered as in a spreadsheet, where the header is row 1.
+pub struct CsvImporter {
+    pub mapping: BTreeMap<String, String>,
+}
+
+impl Importer for CsvImporter {
+    fn read(&self, path: &Path) -> Result<Vec<(String, Parsed)>, Box<dyn Error>> {
+        Ok(read(path, &self.mapping)?
+            .into_iter()
+            .map(|(row, parsed)| (format!("{} row {}", path.display(), row), parsed))
+            .collect())
+    }
+}
+
+fn read(
+    path: &Path,
+    mapping: &BTreeMap<String, String>,
+) -> Result<Vec<(usize, Parsed)>, Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .map_err(|err| format!("{}: {}", path.display(), err))?;
+    let headers = reader
+        .headers()
+        .map_err(|err| format!("{}: {}", path.display(), err))?
+        .clone();
+    let find = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(name))
+    };
+
+    let mut columns = Vec::new();
+    for field in FIELDS {
+        match mapping.get(*field) {
+            Some(column) => {
+                let index = find(column).ok_or_else(|| {
+                    format!(
+                        "{}: no column '{}' for {}; the columns are {}",
+                        path.display(),
+                        column,
+                        field,
+                        headers.iter().collect::<Vec<_>>().join(", ")
+                    )
+                })?;
+                columns.push((*field, index));
+            }
+            None => columns.extend(find(field).map(|index| (*field, index))),
+        }
+    }
+    if !columns.iter().any(|(field, _)| *field == "date") {
+        return Err(format!(
+            "{}: no date column; name one with --map date=<column>",
+            path.display()
+        )
+        .into());
+    }
+
+    let mut rows = Vec::new();
+    for (index, row) in reader.records().enumerate() {
+        let record = row.map_err(|err| err.to_string()).map(|row| {
+            let mut record = Record::default();
+            for (field, column) in &columns {
+                let value = row.get(*column).unwrap_or_default();
+                if *field == "tags" {
+                    record.tags = value
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .map(str::to_string)
+                        .collect();
+                } else {
+                    record.fields.insert(field.to_string(), value.to_string());
+                }
+            }
+            record
+        });
+        rows.push((index + 2, record));
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dream::{Intensity, Style};
+    use chrono::{DateTime, Duration};
+
+    fn importer(mapping: &[(&str, &str)]) -> CsvImporter {
+        let mapping = mapping
+            .iter()
+            .map(|(field, column)| (field.to_string(), column.to_string()))
+            .collect();
+        CsvImporter { mapping }
+    }
+
+    fn import(text: &str, mapping: &[(&str, &str)]) -> Vec<(String, Result<Dream, String>)> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dreams.csv");
+        std::fs::write(&path, text).unwrap();
+        importer(mapping)
+            .read(&path)
+            .unwrap()
+            .into_iter()
+            .map(|(source, parsed)| {
+                let source = source.rsplit_once(' ').unwrap().1.to_string();
+                (source, parsed.and_then(|record| record.into_dream(&Config::default())))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn export_reads_back_unchanged() {
+        let date = DateTime::parse_from_rfc3339("2025-03-14T02:10:00+09:30").unwrap();
+        let mut first = Dream::new(date, Intensity::Medium, Style::Prophetic);
+        first.experience = "A letter, unopened.\nThen a second one, \"urgent\".\n".to_string();
+        first.tags = vec!["letters".to_string(), "home-town".to_string()];
+        first.frequency = 2;
+        first.updated_at = first.created_at + Duration::hours(3);
+        let mut second = Dream::new(date + Duration::days(1), Intensity::Low, Style::Normal);
+        second.experience = "N/A".to_string();
+        let dreams = [first, second];
+
+        let imported = import(&export(&dreams, &Config::default()).unwrap(), &[]);
+        assert_eq!(imported.len(), 2);
+        for (dream, (_, read)) in dreams.iter().zip(imported) {
+            let read = read.unwrap();
+            assert_eq!(read.id, dream.id);
+            assert_eq!(read.date, dream.date);
+            assert_eq!(read.date.offset(), dream.date.offset());
+            assert!(read.intensity == dream.intensity);
+            assert!(read.style == dream.style);
+            assert_eq!(read.frequency, dream.frequency);
+            assert_eq!(read.experience, dream.experience);
+            assert_eq!(read.tags, dream.tags);
+            assert_eq!(read.created_at, dream.created_at);
+            assert_eq!(read.updated_at, dream.updated_at);
+        }
+    }
+
+    #[test]
+    fn imports_mapped_columns_and_reports_bad_rows() {
+        let imported = import(
+            "When,How strong,Kind,Notes,Labels\n\
+             2025-03-14 07:30,HIGH,lucid,\"Flying, then falling\",sky fall\n\
+             2025-03-15 06:00,extreme,normal,Too strong,\n\
+             ,low,normal,No date,\n\
+             2025-03-16 05:00,low,Nightmare,Short row\n",
+            &[
+                ("date", "when"),
+                ("intensity", "How Strong"),
+                ("style", "kind"),
+                ("experience", "notes"),
+                ("tags", "labels"),
+            ],
+        );
+
+        let rows: Vec<&str> = imported.iter().map(|(row, _)| row.as_str()).collect();
+        assert_eq!(rows, ["2", "3", "4", "5"]);
+        let first = imported[0].1.as_ref().unwrap();
+        assert!(first.intensity == Intensity::High);
+        assert!(first.style == Style::Lucid);
+        assert_eq!(first.experience, "Flying, then falling");
+        assert_eq!(first.tags, ["sky", "fall"]);
+        assert_eq!(imported[1].1.as_ref().err().unwrap(), "unknown intensity 'extreme'");
+        assert_eq!(imported[2].1.as_ref().err().unwrap(), "no date");
+        assert!(imported[3].1.as_ref().unwrap().style == Style::Nightmare);
+    }
+
+    #[test]
+    fn explains_missing_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dreams.csv");
+        std::fs::write(&path, "When,Notes\n2025-03-14,Flying\n").unwrap();
+        let error = |mapping: &[(&str, &str)]| importer(mapping).read(&path).err().unwrap().to_string();
+
+        assert!(error(&[]).ends_with("no date column; name one with --map date=<column>"));
+        assert!(error(&[("date", "day")]).ends_with("no column 'day' for date; the columns are When, Notes"));
+    }
+}
@@ -0,0 +1,342 @@
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+    Weekday,
+};
+
+/// Formats tried on the input as a whole before reading it as words.
+const EXACT_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+];
+
+/// The night a local time belongs to: its own day, or the day before if it
+/// is before `cutoff`.
+pub fn night_of(at: NaiveDateTime, cutoff: NaiveTime) -> NaiveDate {
+    if at.time() < cutoff {
+        at.date() - Duration::days(1)
+    } else {
+        at.date()
+    }
+}
+
+/// `naive` read as local time, with the UTC offset in effect then. Of a time
+/// repeated when the clocks go back, the first is taken.
+pub fn from_local(naive: NaiveDateTime) -> Result<DateTime<FixedOffset>, String> {
+    in_zone(&Local, naive)
+}
+
+/// `naive` read as a time in `zone`, as `from_local` reads it.
+fn in_zone<Tz: TimeZone>(zone: &Tz, naive: NaiveDateTime) -> Result<DateTime<FixedOffset>, String> {
+    // `earliest` is not used: `Local` may list the later instant first.
+    match zone.from_local_datetime(&naive) {
+        LocalResult::Single(date) => Ok(date.fixed_offset()),
+        LocalResult::Ambiguous(a, b) => Ok(a.min(b).fixed_offset()),
+        LocalResult::None => Err(format!(
+            "{} is skipped by a clock change here",
+            naive.format("%Y-%m-%d %H:%M")
+        )),
+    }
+}
+
+/// Reads a date and time typed by the user, relative to `now`.
+///
+/// Accepts a date in `date_format` or ISO form, or a day followed by an
+/// optional time: `now`, `today`, `yesterday`, `tomorrow`, a weekday (`mon`,
+/// `last friday`), `3 days ago` / `2 hours ago`, or `2025-01-31`; then
+/// `6am`, `6:30 pm`, `18:45`, `noon` or `midnight`. A relative day keeps the
+/// time of `now`, a calendar date starts at midnight, and a time on its own
+/// means the last time the clock showed it.
+pub fn parse_when(input: &str, now: NaiveDateTime, date_format: &str) -> Result<NaiveDateTime, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("enter a date, such as 'yesterday 6am'".to_string());
+    }
+    for format in std::iter::once(date_format).chain(EXACT_FORMATS.iter().copied()) {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(input, format) {
+            return Ok(datetime);
+        }
+    }
+
+    let lower = input.to_lowercase();
+    let words: Vec<&str> = lower.split_whitespace().filter(|word| *word != "at").collect();
+    let invalid = || {
+        format!(
+            "could not read '{}' as a date; try 'yesterday 6am', '2 days ago' or '2025-01-31 07:30'",
+            input
+        )
+    };
+
+    let (start, rest) = match parse_day(&words, now) {
+        Some((start, used)) => (Some(start), &words[used..]),
+        None => (None, &words[..]),
+    };
+    let time = if rest.is_empty() {
+        None
+    } else {
+        Some(parse_time(&rest.concat()).ok_or_else(invalid)?)
+    };
+
+    match (start, time) {
+        (Some(start), Some(time)) => Ok(start.date().and_time(time)),
+        (Some(start), None) => Ok(start),
+        (None, Some(time)) => {
+            let today = now.date().and_time(time);
+            Ok(if today > now { today - Duration::days(1) } else { today })
+        }
+        (None, None) => Err(invalid()),
+    }
+}
+
+/// The day named by the first words of `words` and how many words it took.
+fn parse_day(words: &[&str], now: NaiveDateTime) -> Option<(NaiveDateTime, usize)> {
+    let first = *words.first()?;
+    match first {
+        "now" | "today" => return Some((now, 1)),
+        "yesterday" => return Some((now - Duration::days(1), 1)),
+        "tomorrow" => return Some((now + Duration::days(1), 1)),
+        _ => {}
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(first, "%Y-%m-%d") {
+        return Some((date.and_time(NaiveTime::MIN), 1));
+    }
+    if let Some(weekday) = parse_weekday(first) {
+        return Some((now - Duration::days(days_back(now.date(), weekday, false)), 1));
+    }
+    if first == "last" {
+        let weekday = parse_weekday(words.get(1)?)?;
+        return Some((now - Duration::days(days_back(now.date(), weekday, true)), 2));
+    }
+
+    // `3 days ago`, or `3d ago`.
+    let (amount, unit, used) = match first.find(|c: char| !c.is_ascii_digit()) {
+        Some(0) => return None,
+        Some(at) => (&first[..at], &first[at..], 2),
+        None => (first, *words.get(1)?, 3),
+    };
+    if words.get(used - 1) != Some(&"ago") {
+        return None;
+    }
+    let amount: i64 = amount.parse().ok()?;
+    // Amounts too large for a date are not read, rather than overflowing.
+    let ago = match unit {
+        "m" | "min" | "mins" | "minute" | "minutes" => Duration::try_minutes(amount),
+        "h" | "hour" | "hours" => Duration::try_hours(amount),
+        "d" | "day" | "days" => Duration::try_days(amount),
+        "w" | "week" | "weeks" => Duration::try_weeks(amount),
+        _ => return None,
+    }?;
+    Some((now.checked_sub_signed(ago)?, used))
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    const NAMES: [(&str, Weekday); 7] = [
+        ("monday", Weekday::Mon),
+        ("tuesday", Weekday::Tue),
+        ("wednesday", Weekday::Wed),
+        ("thursday", Weekday::Thu),
+        ("friday", Weekday::Fri),
+        ("saturday", Weekday::Sat),
+        ("sunday", Weekday::Sun),
+    ];
+    NAMES
+        .iter()
+        .find(|(name, _)| word.len() >= 3 && name.starts_with(word))
+        .map(|(_, weekday)| *weekday)
+}
+
+/// Days from `today` back to the most recent `weekday`; today itself counts
+/// unless `before_today`.
+fn days_back(today: NaiveDate, weekday: Weekday, before_today: bool) -> i64 {
+    let back = (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+    if back == 0 && before_today {
+        7
+    } else {
+        back as i64
+    }
+}
+
+/// `6am`, `6:30pm`, `18:45`, `18:45:10`, `noon` or `midnight`.
+fn parse_time(text: &str) -> Option<NaiveTime> {
+    match text {
+        "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return Some(NaiveTime::MIN),
+        _ => {}
+    }
+    let (clock, pm) = if let Some(clock) = text.strip_suffix("am").or_else(|| text.strip_suffix('a')) {
+        (clock, Some(false))
+    } else if let Some(clock) = text.strip_suffix("pm").or_else(|| text.strip_suffix('p')) {
+        (clock, Some(true))
+    } else {
+        (text, None)
+    };
+
+    let mut parts = clock.split(':');
+    let hour: u32 = parts.next()?.parse().ok()?;
+    let minute: u32 = parts.next().map_or(Some(0), |m| m.parse().ok())?;
+    let second: u32 = parts.next().map_or(Some(0), |s| s.parse().ok())?;
+    if parts.next().is_some() || (pm.is_none() && !clock.contains(':')) {
+        return None;
+    }
+    let hour = match pm {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(pm) => hour % 12 + if pm { 12 } else { 0 },
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Friday 2025-03-14, 09:30.
+    fn now() -> NaiveDateTime {
+        at("2025-03-14 09:30")
+    }
+
+    fn at(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn when(input: &str) -> NaiveDateTime {
+        parse_when(input, now(), "%d/%m/%Y %H:%M").unwrap()
+    }
+
+    #[test]
+    fn reads_relative_days_with_times() {
+        assert_eq!(when("yesterday 6am"), at("2025-03-13 06:00"));
+        assert_eq!(when("Yesterday at 6:30 PM"), at("2025-03-13 18:30"));
+        assert_eq!(when("tomorrow noon"), at("2025-03-15 12:00"));
+        assert_eq!(when("today"), now());
+        assert_eq!(when("2025-01-31"), at("2025-01-31 00:00"));
+        assert_eq!(when("2025-01-31 07:30"), at("2025-01-31 07:30"));
+        assert_eq!(when("31/01/2025 07:30"), at("2025-01-31 07:30"));
+    }
+
+    #[test]
+    fn reads_amounts_ago() {
+        assert_eq!(when("3d ago"), at("2025-03-11 09:30"));
+        assert_eq!(when("3 days ago"), when("3d ago"));
+        assert_eq!(when("2 hours ago"), at("2025-03-14 07:30"));
+        assert_eq!(when("1w ago 7am"), at("2025-03-07 07:00"));
+        assert!(parse_when("3 days", now(), "%d/%m/%Y").is_err());
+        assert!(parse_when("3 fortnights ago", now(), "%d/%m/%Y").is_err());
+    }
+
+    #[test]
+    fn rejects_amounts_too_large_for_a_date() {
+        for input in ["999999999 days ago", "999999999999999 days ago", "99999999999999999999w ago"] {
+            assert!(parse_when(input, now(), "%d/%m/%Y")
+                .err()
+                .unwrap()
+                .starts_with(&format!("could not read '{}' as a date", input)));
+        }
+    }
+
+    #[test]
+    fn reads_weekdays() {
+        // Today is a Friday: `friday` is today, `last friday` a week ago.
+        assert_eq!(when("friday"), now());
+        assert_eq!(when("last friday"), at("2025-03-07 09:30"));
+        assert_eq!(when("wed 11pm"), at("2025-03-12 23:00"));
+        assert_eq!(when("last sat"), at("2025-03-08 09:30"));
+    }
+
+    #[test]
+    fn reads_twelve_oclock() {
+        assert_eq!(when("yesterday 12am"), at("2025-03-13 00:00"));
+        assert_eq!(when("yesterday 12pm"), at("2025-03-13 12:00"));
+        assert_eq!(when("yesterday midnight"), at("2025-03-13 00:00"));
+        assert!(parse_when("yesterday 13pm", now(), "%d/%m/%Y").is_err());
+        assert!(parse_when("yesterday 0am", now(), "%d/%m/%Y").is_err());
+    }
+
+    #[test]
+    fn time_alone_is_the_last_time_it_was_shown() {
+        assert_eq!(when("6am"), at("2025-03-14 06:00"));
+        assert_eq!(when("11pm"), at("2025-03-13 23:00"));
+        assert_eq!(when("9:30"), now());
+    }
+
+    #[test]
+    fn explains_unreadable_input() {
+        assert_eq!(
+            parse_when("", now(), "%d/%m/%Y").err().unwrap(),
+            "enter a date, such as 'yesterday 6am'"
+        );
+        assert!(parse_when("someday", now(), "%d/%m/%Y")
+            .err()
+            .unwrap()
+            .starts_with("could not read 'someday' as a date"));
+    }
+
+    #[test]
+    fn assigns_early_hours_to_the_night_before() {
+        let noon = NaiveTime::from_hms_opt(12, 0, 0).unwrap();
+        assert_eq!(night_of(at("2025-03-14 03:00"), noon), at("2025-03-13 00:00").date());
+        assert_eq!(night_of(at("2025-03-14 23:00"), noon), at("2025-03-14 00:00").date());
+    }
+
+    #[test]
+    fn reads_local_times_across_clock_changes() {
+        assert_eq!(
+            in_zone(&CentralEurope, at("2025-03-30 02:30")).err().unwrap(),
+            "2025-03-30 02:30 is skipped by a clock change here"
+        );
+        assert_eq!(
+            in_zone(&CentralEurope, at("2025-03-30 03:30")).unwrap().to_rfc3339(),
+            "2025-03-30T03:30:00+02:00"
+        );
+        // The first 02:30, before the clocks went back.
+        assert_eq!(
+            in_zone(&CentralEurope, at("2025-10-26 02:30")).unwrap().to_rfc3339(),
+            "2025-10-26T02:30:00+02:00"
+        );
+        assert_eq!(
+            in_zone(&CentralEurope, at("2025-10-26 03:30")).unwrap().to_rfc3339(),
+            "2025-10-26T03:30:00+01:00"
+        );
+    }
+
+    /// Central European time in 2025: the clocks went from 02:00 to 03:00 on
+    /// 2025-03-30 and back from 03:00 to 02:00 on 2025-10-26.
+    #[derive(Clone)]
+    struct CentralEurope;
+
+    impl TimeZone for CentralEurope {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            CentralEurope
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_hms_opt(12, 0, 0).unwrap())
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let (winter, summer) = (FixedOffset::east_opt(3600).unwrap(), FixedOffset::east_opt(7200).unwrap());
+            if (at("2025-03-30 02:00")..at("2025-03-30 03:00")).contains(local) {
+                LocalResult::None
+            } else if (at("2025-10-26 02:00")..at("2025-10-26 03:00")).contains(local) {
+                // The later instant first, as `Local` may list them.
+                LocalResult::Ambiguous(winter, summer)
+            } else if (at("2025-03-30 03:00")..at("2025-10-26 03:00")).contains(local) {
+                LocalResult::Single(summer)
+            } else {
+                LocalResult::Single(winter)
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_hms_opt(12, 0, 0).unwrap())
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            let summer = (at("2025-03-30 01:00")..at("2025-10-26 01:00")).contains(utc);
+            FixedOffset::east_opt(if summer { 7200 } else { 3600 }).unwrap()
+        }
+    }
+}
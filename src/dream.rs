@@ -1,7 +1,11 @@
+use crate::{constants::DATE_FORMAT, dates::night_of};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use serde::{Serialize, Deserialize};
-use std::fmt;
+use std::{fmt, str::FromStr};
+use uuid::Uuid;
 
-#[derive(Serialize, Deserialize, Clone, PartialEq)]
+/// Ordered from `Low` to `High`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Intensity {
     Low,
     Medium,
@@ -18,7 +22,20 @@ impl fmt::Display for Intensity {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+impl FromStr for Intensity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "low" => Ok(Intensity::Low),
+            "medium" => Ok(Intensity::Medium),
+            "high" => Ok(Intensity::High),
+            _ => Err(format!("unknown intensity '{}'", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub enum Style {
     Lucid,
     Nightmare,
@@ -39,11 +56,119 @@ impl fmt::Display for Style {
     }
 }
 
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "lucid" => Ok(Style::Lucid),
+            "nightmare" => Ok(Style::Nightmare),
+            "recurring" => Ok(Style::Recurring),
+            "prophetic" => Ok(Style::Prophetic),
+            "normal" => Ok(Style::Normal),
+            _ => Err(format!("unknown style '{}'", s)),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Dream {
-    pub date: String,
+    pub id: Uuid,
+    /// When the dream happened, with the UTC offset it was recorded in.
+    pub date: DateTime<FixedOffset>,
     pub intensity: Intensity,
     pub experience: String,
     pub frequency: u8,
     pub style: Style,
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Dream {
+    pub fn new(date: DateTime<FixedOffset>, intensity: Intensity, style: Style) -> Dream {
+        let now = Utc::now();
+        Dream {
+            id: Uuid::new_v4(),
+            date,
+            intensity,
+            experience: String::new(),
+            frequency: 0,
+            style,
+            tags: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// When the dream happened, in the local time zone.
+    pub fn local_date(&self) -> DateTime<Local> {
+        self.date.with_timezone(&Local)
+    }
+
+    /// `date` in local time, written with `date_format`.
+    pub fn shown_date(&self, date_format: &str) -> String {
+        self.local_date().format(date_format).to_string()
+    }
+
+    /// The night the dream is filed under, in local time.
+    pub fn night(&self, cutoff: NaiveTime) -> NaiveDate {
+        night_of(self.local_date().naive_local(), cutoff)
+    }
+
+    /// The first eight characters of the id, enough to address a dream.
+    pub fn short_id(&self) -> String {
+        self.id.to_string()[..8].to_string()
+    }
+}
+
+/// Lowercases a tag and joins its words with `-`; `None` if nothing is left.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase();
+    (!tag.is_empty()).then_some(tag)
+}
+
+/// Splits tags separated by commas or whitespace, sorted and without duplicates.
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter_map(normalize_tag)
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// When a dream without timestamps was recorded: its `date`, if that parses.
+pub fn legacy_timestamp(date: &str, date_format: &str) -> Option<DateTime<Utc>> {
+    legacy_date(date, date_format).map(|date| date.with_timezone(&Utc))
+}
+
+/// Reads a `date` written before dates carried an offset. Those were the
+/// UTC time formatted with `date_format` or the default one, or typed by hand.
+pub fn legacy_date(date: &str, date_format: &str) -> Option<DateTime<FixedOffset>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date);
+    }
+    let naive = NaiveDateTime::parse_from_str(date, date_format)
+        .or_else(|_| NaiveDateTime::parse_from_str(date, DATE_FORMAT))
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M"))
+        .or_else(|_| NaiveDate::parse_from_str(date, "%Y-%m-%d").map(|day| day.and_time(NaiveTime::MIN)))
+        .ok()?;
+    Some(naive.and_utc().fixed_offset())
+}
+
+/// Why a migration stopped at `dates` that `legacy_date` cannot read. They
+/// are left as they are rather than guessed.
+pub fn unreadable_dates(dates: &[String], date_format: &str) -> String {
+    let quoted: Vec<String> = dates.iter().map(|date| format!("'{}'", date)).collect();
+    format!(
+        "unreadable dates {}; set date_format (now '{}') to the format they were written in",
+        quoted.join(", "),
+        date_format
+    )
 }
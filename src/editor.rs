@@ -0,0 +1,517 @@
+use crate::paths::memory_dir;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{error::Error, io::Write, ops::Range, process};
+
+/// A place in the text as (line, column), counted in characters.
+pub type Position = (usize, usize);
+
+/// A multi-line text buffer with a cursor, an optional selection and a
+/// viewport that follows the cursor. Lines are soft-wrapped to the area the
+/// editor was last drawn in, and Up/Down move by wrapped row.
+pub struct TextEditor {
+    /// Never empty; an empty text is one empty line.
+    lines: Vec<Vec<char>>,
+    cursor: Position,
+    /// The other end of the selection, if there is one.
+    anchor: Option<Position>,
+    /// Column to aim for while moving up and down past shorter rows.
+    goal_column: Option<usize>,
+    /// First wrapped row shown.
+    scroll: usize,
+    width: usize,
+    height: usize,
+}
+
+/// One wrapped row: a line and the range of its characters shown on the row.
+pub struct Row {
+    pub line: usize,
+    pub columns: Range<usize>,
+}
+
+impl Default for TextEditor {
+    fn default() -> TextEditor {
+        TextEditor::new("")
+    }
+}
+
+impl TextEditor {
+    pub fn new(text: &str) -> TextEditor {
+        TextEditor {
+            lines: text.split('\n').map(|line| line.chars().collect()).collect(),
+            cursor: (0, 0),
+            anchor: None,
+            goal_column: None,
+            scroll: 0,
+            width: 80,
+            height: 10,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn line(&self, index: usize) -> &[char] {
+        &self.lines[index]
+    }
+
+    /// The selected range, start first.
+    pub fn selection(&self) -> Option<(Position, Position)> {
+        let anchor = self.anchor.filter(|anchor| *anchor != self.cursor)?;
+        Some((anchor.min(self.cursor), anchor.max(self.cursor)))
+    }
+
+    /// Applies an editing or movement key. Returns false for keys it does
+    /// not handle, so the caller can use them.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let line = self.cursor.0;
+
+        match key.code {
+            KeyCode::Left if ctrl || alt => self.move_to(self.word_left(self.cursor), shift),
+            KeyCode::Left => self.move_to(self.char_left(self.cursor), shift),
+            KeyCode::Right if ctrl || alt => self.move_to(self.word_right(self.cursor), shift),
+            KeyCode::Right => self.move_to(self.char_right(self.cursor), shift),
+            KeyCode::Up => self.move_rows(-1, shift),
+            KeyCode::Down => self.move_rows(1, shift),
+            KeyCode::PageUp => self.move_rows(-(self.height.max(1) as isize), shift),
+            KeyCode::PageDown => self.move_rows(self.height.max(1) as isize, shift),
+            KeyCode::Home if ctrl => self.move_to((0, 0), shift),
+            KeyCode::Home => self.move_to((line, 0), shift),
+            KeyCode::End if ctrl => self.move_to(self.end(), shift),
+            KeyCode::End => self.move_to((line, self.lines[line].len()), shift),
+            KeyCode::Backspace if ctrl || alt => self.delete_word_back(),
+            KeyCode::Char('w') if ctrl => self.delete_word_back(),
+            KeyCode::Backspace => {
+                if !self.delete_selection() {
+                    self.delete(self.char_left(self.cursor), self.cursor);
+                }
+            }
+            KeyCode::Delete => {
+                if !self.delete_selection() {
+                    self.delete(self.cursor, self.char_right(self.cursor));
+                }
+            }
+            KeyCode::Enter => self.insert('\n'),
+            KeyCode::Char(_) if ctrl || alt => return false,
+            KeyCode::Char(c) => self.insert(c),
+            _ => return false,
+        }
+        if !matches!(
+            key.code,
+            KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown
+        ) {
+            self.goal_column = None;
+        }
+        self.scroll_to_cursor();
+        true
+    }
+
+    /// Sets the size of the area the text is drawn in and scrolls so the
+    /// cursor stays visible.
+    pub fn set_viewport(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.scroll_to_cursor();
+    }
+
+    /// The wrapped rows currently in view and the cursor's place among them
+    /// as (column, row), if it is in view.
+    pub fn visible_rows(&self) -> (Vec<Row>, Option<(usize, usize)>) {
+        let rows = self.rows();
+        let (row, column) = self.row_of(&rows, self.cursor);
+        let cursor = (row >= self.scroll && row < self.scroll + self.height)
+            .then(|| (column, row - self.scroll));
+        let visible = rows.into_iter().skip(self.scroll).take(self.height).collect();
+        (visible, cursor)
+    }
+
+    fn insert(&mut self, c: char) {
+        self.delete_selection();
+        let (line, column) = self.cursor;
+        if c == '\n' {
+            let rest = self.lines[line].split_off(column);
+            self.lines.insert(line + 1, rest);
+            self.cursor = (line + 1, 0);
+        } else {
+            self.lines[line].insert(column, c);
+            self.cursor = (line, column + 1);
+        }
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                self.delete(start, end);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn delete_word_back(&mut self) {
+        if !self.delete_selection() {
+            self.delete(self.word_left(self.cursor), self.cursor);
+        }
+    }
+
+    /// Removes the text from `start` up to `end` and leaves the cursor there.
+    fn delete(&mut self, start: Position, end: Position) {
+        if start < end {
+            let tail = self.lines[end.0].split_off(end.1);
+            self.lines[start.0].truncate(start.1);
+            self.lines[start.0].extend(tail);
+            self.lines.drain(start.0 + 1..=end.0);
+        }
+        self.cursor = start;
+        self.anchor = None;
+    }
+
+    fn move_to(&mut self, position: Position, extend: bool) {
+        if extend {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = position;
+    }
+
+    /// Moves the cursor `by` wrapped rows, keeping to the goal column.
+    fn move_rows(&mut self, by: isize, extend: bool) {
+        let rows = self.rows();
+        let (row, column) = self.row_of(&rows, self.cursor);
+        let goal = *self.goal_column.get_or_insert(column);
+        let target = (row as isize + by).clamp(0, rows.len() as isize - 1) as usize;
+        let target_row = &rows[target];
+        // Only the last row of a line may put the cursor after its last character.
+        let last_of_line = rows
+            .get(target + 1)
+            .is_none_or(|next| next.line != target_row.line);
+        let max = if last_of_line {
+            target_row.columns.len()
+        } else {
+            target_row.columns.len().saturating_sub(1)
+        };
+        self.move_to(
+            (target_row.line, target_row.columns.start + goal.min(max)),
+            extend,
+        );
+    }
+
+    fn char_left(&self, (line, column): Position) -> Position {
+        match (line, column) {
+            (0, 0) => (0, 0),
+            (line, 0) => (line - 1, self.lines[line - 1].len()),
+            (line, column) => (line, column - 1),
+        }
+    }
+
+    fn char_right(&self, (line, column): Position) -> Position {
+        if column < self.lines[line].len() {
+            (line, column + 1)
+        } else if line + 1 < self.lines.len() {
+            (line + 1, 0)
+        } else {
+            (line, column)
+        }
+    }
+
+    /// The start of the word before the cursor; a line break counts as a word.
+    fn word_left(&self, (line, mut column): Position) -> Position {
+        if column == 0 {
+            return self.char_left((line, column));
+        }
+        let chars = &self.lines[line];
+        while column > 0 && !is_word(chars[column - 1]) {
+            column -= 1;
+        }
+        while column > 0 && is_word(chars[column - 1]) {
+            column -= 1;
+        }
+        (line, column)
+    }
+
+    /// The end of the word after the cursor; a line break counts as a word.
+    fn word_right(&self, (line, mut column): Position) -> Position {
+        let chars = &self.lines[line];
+        if column == chars.len() {
+            return self.char_right((line, column));
+        }
+        while column < chars.len() && !is_word(chars[column]) {
+            column += 1;
+        }
+        while column < chars.len() && is_word(chars[column]) {
+            column += 1;
+        }
+        (line, column)
+    }
+
+    fn end(&self) -> Position {
+        let last = self.lines.len() - 1;
+        (last, self.lines[last].len())
+    }
+
+    fn scroll_to_cursor(&mut self) {
+        let rows = self.rows();
+        let (row, _) = self.row_of(&rows, self.cursor);
+        if row < self.scroll {
+            self.scroll = row;
+        } else if row >= self.scroll + self.height {
+            self.scroll = row + 1 - self.height.max(1);
+        }
+        self.scroll = self.scroll.min(rows.len().saturating_sub(1));
+    }
+
+    /// Every line wrapped at word boundaries. One column is kept free so the
+    /// cursor fits after the last character of a full row.
+    fn rows(&self) -> Vec<Row> {
+        let width = self.width.saturating_sub(1).max(1);
+        let mut rows = Vec::new();
+        for (line, chars) in self.lines.iter().enumerate() {
+            let mut start = 0;
+            loop {
+                let mut end = (start + width).min(chars.len());
+                if end < chars.len() {
+                    if let Some(space) = chars[start..end].iter().rposition(|c| c.is_whitespace()) {
+                        end = start + space + 1;
+                    }
+                }
+                rows.push(Row {
+                    line,
+                    columns: start..end,
+                });
+                if end >= chars.len() {
+                    break;
+                }
+                start = end;
+            }
+        }
+        rows
+    }
+
+    /// The wrapped row holding `position` and the column within it.
+    fn row_of(&self, rows: &[Row], (line, column): Position) -> (usize, usize) {
+        let mut found = 0;
+        for (index, row) in rows.iter().enumerate() {
+            if row.line == line {
+                found = index;
+                if column < row.columns.end {
+                    break;
+                }
+            } else if row.line > line {
+                break;
+            }
+        }
+        (found, column - rows[found].columns.start)
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Edits `text` in `$VISUAL` or `$EDITOR` (falling back to `vi`) through a
+/// temporary file and returns the result. The caller must have handed the
+/// terminal over first. A `private` text, from an encrypted journal, is only
+/// written to a directory kept in memory.
+pub fn edit_externally(text: &str, private: bool) -> Result<String, Box<dyn Error>> {
+    let editor = std::env::var("VISUAL")
+        .ok()
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+
+    let mut builder = tempfile::Builder::new();
+    builder.prefix("dream-").suffix(".txt");
+    let mut file = if private {
+        let dir = memory_dir().ok_or(
+            "the journal is encrypted and there is no in-memory directory ($XDG_RUNTIME_DIR) for the editor's file; use the built-in editor",
+        )?;
+        builder.tempfile_in(dir)?
+    } else {
+        builder.tempfile()?
+    };
+    file.write_all(text.as_bytes())?;
+    file.flush()?;
+
+    // Run through the shell, as git does, so settings like `code --wait` work.
+    let status = if cfg!(windows) {
+        process::Command::new("cmd")
+            .arg("/C")
+            .arg(format!("{} \"{}\"", editor, file.path().display()))
+            .status()
+    } else {
+        process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg(&editor)
+            .arg(file.path())
+            .status()
+    }
+    .map_err(|err| format!("could not run '{}': {}", editor, err))?;
+    if !status.success() {
+        return Err(format!("'{}' exited with {}", editor, status).into());
+    }
+
+    let edited = std::fs::read_to_string(file.path())?;
+    // Editors usually end the file with a newline the text did not have.
+    Ok(edited.trim_end_matches(['\n', '\r']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(editor: &mut TextEditor, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        editor.handle_key(KeyEvent::new(code, modifiers))
+    }
+
+    fn type_text(editor: &mut TextEditor, text: &str) {
+        for c in text.chars() {
+            press(editor, KeyCode::Char(c), KeyModifiers::NONE);
+        }
+    }
+
+    const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+    const SHIFT: KeyModifiers = KeyModifiers::SHIFT;
+
+    #[test]
+    fn moves_by_word_around_punctuation_and_multi_byte_chars() {
+        let mut editor = TextEditor::new("first\nhéllo, wörld—ok");
+        press(&mut editor, KeyCode::End, CTRL);
+        assert_eq!(editor.cursor, (1, 15));
+
+        let mut stops = Vec::new();
+        for _ in 0..5 {
+            press(&mut editor, KeyCode::Left, CTRL);
+            stops.push(editor.cursor);
+        }
+        // A line break counts as a word.
+        assert_eq!(stops, [(1, 13), (1, 7), (1, 0), (0, 5), (0, 0)]);
+
+        press(&mut editor, KeyCode::Down, KeyModifiers::NONE);
+        let mut stops = Vec::new();
+        for _ in 0..4 {
+            press(&mut editor, KeyCode::Right, KeyModifiers::ALT);
+            stops.push(editor.cursor);
+        }
+        assert_eq!(stops, [(1, 5), (1, 12), (1, 15), (1, 15)]);
+
+        press(&mut editor, KeyCode::Left, CTRL);
+        press(&mut editor, KeyCode::Backspace, CTRL);
+        assert_eq!(editor.text(), "first\nhéllo, ok");
+    }
+
+    #[test]
+    fn deletes_or_replaces_the_selection() {
+        let mut editor = TextEditor::new("one two three");
+        for _ in 0..4 {
+            press(&mut editor, KeyCode::Right, SHIFT);
+        }
+        assert_eq!(editor.selection(), Some(((0, 0), (0, 4))));
+        press(&mut editor, KeyCode::Backspace, KeyModifiers::NONE);
+        assert_eq!(editor.text(), "two three");
+        assert_eq!(editor.selection(), None);
+
+        press(&mut editor, KeyCode::Right, CTRL | SHIFT);
+        type_text(&mut editor, "2");
+        assert_eq!(editor.text(), "2 three");
+
+        // Selecting backwards across lines, then deleting.
+        let mut editor = TextEditor::new("ab\ncd");
+        press(&mut editor, KeyCode::End, CTRL);
+        press(&mut editor, KeyCode::Left, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Up, SHIFT);
+        assert_eq!(editor.selection(), Some(((0, 1), (1, 1))));
+        press(&mut editor, KeyCode::Delete, KeyModifiers::NONE);
+        assert_eq!(editor.text(), "ad");
+        assert_eq!(editor.cursor, (0, 1));
+    }
+
+    #[test]
+    fn ctrl_w_at_the_start_of_a_line_joins_it_to_the_one_before() {
+        let mut editor = TextEditor::new("first line\nsecond");
+        press(&mut editor, KeyCode::End, CTRL);
+        press(&mut editor, KeyCode::Home, KeyModifiers::NONE);
+        assert!(press(&mut editor, KeyCode::Char('w'), CTRL));
+        assert_eq!(editor.text(), "first linesecond");
+        assert_eq!(editor.cursor, (0, 10));
+
+        press(&mut editor, KeyCode::Char('w'), CTRL);
+        assert_eq!(editor.text(), "first second");
+
+        press(&mut editor, KeyCode::Home, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Char('w'), CTRL);
+        assert_eq!(editor.text(), "first second");
+        assert_eq!(editor.cursor, (0, 0));
+
+        // Other control keys are left to the caller.
+        assert!(!press(&mut editor, KeyCode::Char('s'), CTRL));
+    }
+
+    #[test]
+    fn moves_up_and_down_by_wrapped_row() {
+        let mut editor = TextEditor::new("aaaa bbbb cccc\nshort\ndddddddddddddddddddd");
+        editor.set_viewport(11, 10);
+        let rows: Vec<_> = editor
+            .rows()
+            .into_iter()
+            .map(|row| (row.line, row.columns))
+            .collect();
+        assert_eq!(rows, [(0, 0..10), (0, 10..14), (1, 0..5), (2, 0..10), (2, 10..20)]);
+
+        for _ in 0..8 {
+            press(&mut editor, KeyCode::Right, KeyModifiers::NONE);
+        }
+        let mut stops = Vec::new();
+        for _ in 0..4 {
+            press(&mut editor, KeyCode::Down, KeyModifiers::NONE);
+            stops.push(editor.cursor);
+        }
+        // Shorter rows clamp the cursor, which goes back to column 8 after.
+        assert_eq!(stops, [(0, 14), (1, 5), (2, 8), (2, 18)]);
+        for _ in 0..4 {
+            press(&mut editor, KeyCode::Up, KeyModifiers::NONE);
+        }
+        assert_eq!(editor.cursor, (0, 8));
+
+        // Only the last row of a line puts the cursor after its last character.
+        press(&mut editor, KeyCode::End, CTRL);
+        press(&mut editor, KeyCode::Up, KeyModifiers::NONE);
+        assert_eq!(editor.cursor, (2, 9));
+    }
+
+    #[test]
+    fn scrolls_to_follow_the_cursor() {
+        let mut editor = TextEditor::new("1\n2\n3\n4\n5");
+        editor.set_viewport(11, 2);
+        let (rows, cursor) = editor.visible_rows();
+        assert_eq!(rows.iter().map(|row| row.line).collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(cursor, Some((0, 0)));
+
+        for _ in 0..3 {
+            press(&mut editor, KeyCode::Down, KeyModifiers::NONE);
+        }
+        let (rows, cursor) = editor.visible_rows();
+        assert_eq!(rows.iter().map(|row| row.line).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(cursor, Some((0, 1)));
+
+        press(&mut editor, KeyCode::PageUp, KeyModifiers::NONE);
+        let (rows, cursor) = editor.visible_rows();
+        assert_eq!(rows.iter().map(|row| row.line).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(cursor, Some((0, 0)));
+
+        // A smaller viewport still shows the cursor.
+        press(&mut editor, KeyCode::End, CTRL);
+        editor.set_viewport(11, 1);
+        let (rows, cursor) = editor.visible_rows();
+        assert_eq!(rows.iter().map(|row| row.line).collect::<Vec<_>>(), [4]);
+        assert_eq!(cursor, Some((1, 0)));
+    }
+}
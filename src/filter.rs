@@ -0,0 +1,281 @@
+use crate::{
+    config::Config,
+    dream::{normalize_tag, Dream, Intensity, Style},
+    search::{match_ranges, searchable_text, terms},
+};
+use chrono::NaiveDate;
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+/// A parsed filter expression such as
+/// `style:nightmare intensity>=medium tag:water after:2025-01-01 freq>5`.
+///
+/// Clauses are separated by whitespace and must all hold. A clause is
+/// `field<op>value` with op one of `:` `=` `!=` `<` `<=` `>` `>=`, or a bare
+/// word matched like a search. A leading `-` negates a clause.
+#[derive(Clone)]
+pub struct Filter {
+    source: String,
+    clauses: Vec<Clause>,
+}
+
+#[derive(Clone)]
+pub struct Clause {
+    pub negated: bool,
+    pub condition: Condition,
+}
+
+#[derive(Clone)]
+pub enum Condition {
+    Style(Style),
+    Intensity(Cmp, Intensity),
+    Tag(String),
+    Frequency(Cmp, u8),
+    Date(Cmp, NaiveDate),
+    Text(String),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Cmp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Cmp::Eq => ordering == Ordering::Equal,
+            Cmp::Lt => ordering == Ordering::Less,
+            Cmp::Le => ordering != Ordering::Greater,
+            Cmp::Gt => ordering == Ordering::Greater,
+            Cmp::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// Operators in the order they are tried, longest first.
+const OPERATORS: &[(&str, Cmp, bool)] = &[
+    ("<=", Cmp::Le, false),
+    (">=", Cmp::Ge, false),
+    ("!=", Cmp::Eq, true),
+    ("<", Cmp::Lt, false),
+    (">", Cmp::Gt, false),
+    ("=", Cmp::Eq, false),
+    (":", Cmp::Eq, false),
+];
+
+impl Filter {
+    /// Whether `dream` satisfies every clause. Dates are compared by the
+    /// night the dream belongs to.
+    pub fn matches(&self, dream: &Dream, config: &Config) -> bool {
+        self.clauses
+            .iter()
+            .all(|clause| clause.condition.matches(dream, config) != clause.negated)
+    }
+}
+
+impl Condition {
+    fn matches(&self, dream: &Dream, config: &Config) -> bool {
+        match self {
+            Condition::Style(style) => dream.style == *style,
+            Condition::Intensity(cmp, intensity) => cmp.holds(dream.intensity.cmp(intensity)),
+            Condition::Tag(tag) => dream.tags.contains(tag),
+            Condition::Frequency(cmp, frequency) => cmp.holds(dream.frequency.cmp(frequency)),
+            Condition::Date(cmp, date) => cmp.holds(dream.night(config.night_cutoff).cmp(date)),
+            Condition::Text(term) => {
+                !match_ranges(&searchable_text(dream, &config.date_format), &terms(term)).is_empty()
+            }
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let clauses = s
+            .split_whitespace()
+            .map(parse_clause)
+            .collect::<Result<_, _>>()?;
+        Ok(Filter {
+            source: s.split_whitespace().collect::<Vec<_>>().join(" "),
+            clauses,
+        })
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn parse_clause(token: &str) -> Result<Clause, String> {
+    let (negated, token) = match token.strip_prefix('-') {
+        Some(rest) if !rest.is_empty() => (true, rest),
+        _ => (false, token),
+    };
+
+    let Some(at) = token.find([':', '=', '<', '>', '!']) else {
+        return Ok(Clause {
+            negated,
+            condition: Condition::Text(token.to_string()),
+        });
+    };
+    let field = token[..at].to_ascii_lowercase();
+    let (symbol, cmp, negate) = OPERATORS
+        .iter()
+        .copied()
+        .find(|(symbol, _, _)| token[at..].starts_with(symbol))
+        .ok_or_else(|| format!("unknown operator in '{}'", token))?;
+    let value = &token[at + symbol.len()..];
+    if value.is_empty() {
+        return Err(format!("'{}' is missing a value", token));
+    }
+
+    let equality_only = |condition: Condition| {
+        if cmp == Cmp::Eq {
+            Ok(condition)
+        } else {
+            Err(format!("'{}' only supports ':' and '!='", field))
+        }
+    };
+    let condition = match field.as_str() {
+        "style" => equality_only(Condition::Style(value.parse()?))?,
+        "intensity" => Condition::Intensity(cmp, value.parse()?),
+        "tag" => equality_only(Condition::Tag(
+            normalize_tag(value).ok_or_else(|| format!("'{}' is missing a value", token))?,
+        ))?,
+        "freq" | "frequency" => Condition::Frequency(
+            cmp,
+            value
+                .parse()
+                .ok()
+                .filter(|frequency| *frequency <= 10)
+                .ok_or_else(|| format!("frequency must be a number from 0 to 10, got '{}'", value))?,
+        ),
+        "date" => Condition::Date(cmp, parse_date(value)?),
+        "after" | "before" if symbol != ":" => {
+            return Err(format!("write '{}:{}'", field, value));
+        }
+        "after" => Condition::Date(Cmp::Gt, parse_date(value)?),
+        "before" => Condition::Date(Cmp::Lt, parse_date(value)?),
+        _ => {
+            return Err(format!(
+                "unknown field '{}' (expected style, intensity, tag, freq, date, after or before)",
+                field
+            ))
+        }
+    };
+    Ok(Clause {
+        negated: negated != negate,
+        condition,
+    })
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("invalid date '{}', expected YYYY-MM-DD", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dates::from_local;
+
+    fn dream(date: &str, intensity: Intensity, style: Style, frequency: u8, tags: &[&str]) -> Dream {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap().and_hms_opt(2, 0, 0).unwrap();
+        let mut dream = Dream::new(from_local(date).unwrap(), intensity, style);
+        dream.frequency = frequency;
+        dream.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        dream.experience = "Swimming through a flooded library.".to_string();
+        dream
+    }
+
+    fn matches(filter: &str, dream: &Dream) -> bool {
+        filter.parse::<Filter>().unwrap().matches(dream, &Config::default())
+    }
+
+    fn error(filter: &str) -> String {
+        filter.parse::<Filter>().err().unwrap()
+    }
+
+    #[test]
+    fn compares_with_the_longest_operator() {
+        let low = dream("2025-03-10", Intensity::Low, Style::Normal, 5, &[]);
+        let medium = dream("2025-03-10", Intensity::Medium, Style::Normal, 5, &[]);
+        let high = dream("2025-03-10", Intensity::High, Style::Normal, 5, &[]);
+
+        assert!(matches("intensity<=medium", &low));
+        assert!(matches("intensity<=medium", &medium));
+        assert!(!matches("intensity<=medium", &high));
+        assert!(matches("intensity<medium", &low));
+        assert!(!matches("intensity<medium", &medium));
+        assert!(matches("intensity>=medium", &medium));
+        assert!(matches("intensity>=medium", &high));
+        assert!(!matches("intensity>=medium", &low));
+        assert!(!matches("intensity!=medium", &medium));
+        assert!(matches("intensity!=medium", &high));
+        assert!(matches("Intensity=HIGH", &high));
+    }
+
+    #[test]
+    fn negates_clauses() {
+        let nightmare = dream("2025-03-10", Intensity::High, Style::Nightmare, 0, &["water"]);
+
+        assert!(matches("style:nightmare", &nightmare));
+        assert!(!matches("-style:nightmare", &nightmare));
+        assert!(matches("-style!=nightmare", &nightmare));
+        assert!(!matches("-tag:water", &nightmare));
+        assert!(matches("library", &nightmare));
+        assert!(!matches("-library", &nightmare));
+        assert!(matches("-desert", &nightmare));
+    }
+
+    #[test]
+    fn filters_by_frequency() {
+        let often = dream("2025-03-10", Intensity::Low, Style::Recurring, 7, &[]);
+        let once = dream("2025-03-10", Intensity::Low, Style::Recurring, 5, &[]);
+
+        assert!(matches("freq>5", &often));
+        assert!(!matches("freq>5", &once));
+        assert!(matches("frequency=5", &once));
+        assert!(matches("freq<=5", &once));
+    }
+
+    #[test]
+    fn filters_by_night() {
+        let dream = dream("2025-03-10", Intensity::Low, Style::Normal, 0, &[]);
+        // 02:00 belongs to the night that started the day before.
+        assert!(matches("date:2025-03-09", &dream));
+        assert!(!matches("date:2025-03-10", &dream));
+        assert!(matches("after:2025-03-08", &dream));
+        assert!(!matches("after:2025-03-09", &dream));
+        assert!(matches("before:2025-03-10", &dream));
+        assert!(!matches("before:2025-03-09", &dream));
+        assert!(matches("after:2025-03-01 before:2025-03-31 style:normal", &dream));
+    }
+
+    #[test]
+    fn explains_invalid_filters() {
+        assert_eq!(error("freq>11"), "frequency must be a number from 0 to 10, got '11'");
+        assert_eq!(error("freq>lots"), "frequency must be a number from 0 to 10, got 'lots'");
+        assert_eq!(error("date:yesterday"), "invalid date 'yesterday', expected YYYY-MM-DD");
+        assert_eq!(error("after>2025-01-01"), "write 'after:2025-01-01'");
+        assert_eq!(error("style<lucid"), "'style' only supports ':' and '!='");
+        assert_eq!(error("tag:"), "'tag:' is missing a value");
+        assert_eq!(
+            error("mood:calm"),
+            "unknown field 'mood' (expected style, intensity, tag, freq, date, after or before)"
+        );
+        assert_eq!(error("style!lucid"), "unknown operator in 'style!lucid'");
+    }
+
+    #[test]
+    fn keeps_the_source_text() {
+        let filter: Filter = "  style:lucid   tag:water ".parse().unwrap();
+        assert_eq!(filter.to_string(), "style:lucid tag:water");
+    }
+}
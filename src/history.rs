@@ -0,0 +1,313 @@
+use crate::{
+    constants::HISTORY_LIMIT,
+    crypt::{self, Key},
+    dream::Dream,
+    schema::upgrade_entry,
+    store::write_atomic,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+/// A journal mutation with everything needed to reverse it.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Command {
+    Add { dream: Dream },
+    Edit { before: Dream, after: Dream },
+    Delete { dream: Dream, index: usize },
+}
+
+impl Command {
+    /// The dream the command changed.
+    pub fn dream_id(&self) -> Uuid {
+        match self {
+            Command::Add { dream } | Command::Delete { dream, .. } => dream.id,
+            Command::Edit { after, .. } => after.id,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Command::Add { dream } => format!("add of {}", dream.short_id()),
+            Command::Edit { after, .. } => format!("edit of {}", after.short_id()),
+            Command::Delete { dream, .. } => format!("delete of {}", dream.short_id()),
+        }
+    }
+}
+
+/// Undo and redo stacks. Every command gets a serial number, so the saved
+/// state is still recognised after undoing past it and branching off.
+#[derive(Serialize, Deserialize)]
+pub struct History {
+    undo: Vec<(u64, Command)>,
+    redo: Vec<(u64, Command)>,
+    next_serial: u64,
+    /// Serial of the newest applied command when the journal was last saved;
+    /// 0 for none, `None` if no state in the history was ever saved.
+    #[serde(skip)]
+    saved: Option<u64>,
+}
+
+impl Default for History {
+    fn default() -> History {
+        History {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            next_serial: 1,
+            saved: Some(0),
+        }
+    }
+}
+
+impl History {
+    /// Reads the history kept next to `journal`; a missing file is an empty
+    /// history. The journal on disk is taken to match its current state.
+    /// The history is encrypted with the journal's key.
+    pub fn load(journal: &Path, key: Option<&Key>, date_format: &str) -> Result<History, Box<dyn Error>> {
+        let mut history: History = match std::fs::read(history_path(journal)) {
+            Ok(data) => {
+                let mut value: Value = serde_json::from_slice(&crypt::open(&data, key)?)?;
+                upgrade_dreams(&mut value, date_format)?;
+                serde_json::from_value(value)?
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => History::default(),
+            Err(err) => return Err(err.into()),
+        };
+        history.mark_saved();
+        Ok(history)
+    }
+
+    pub fn save(&self, journal: &Path, key: Option<&Key>) -> Result<(), Box<dyn Error>> {
+        let data = crypt::seal(serde_json::to_string(self)?.as_bytes(), key)?;
+        write_atomic(&history_path(journal), &data)
+    }
+
+    /// Records a command that has just been applied, forgetting anything
+    /// that could be redone.
+    pub fn push(&mut self, command: Command) {
+        self.undo.push((self.next_serial, command));
+        self.next_serial += 1;
+        self.redo.clear();
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+    }
+
+    /// The command to reverse next, moved onto the redo stack.
+    pub fn undo(&mut self) -> Option<Command> {
+        let entry = self.undo.pop()?;
+        let command = entry.1.clone();
+        self.redo.push(entry);
+        Some(command)
+    }
+
+    /// The command to apply again next, moved back onto the undo stack.
+    pub fn redo(&mut self) -> Option<Command> {
+        let entry = self.redo.pop()?;
+        let command = entry.1.clone();
+        self.undo.push(entry);
+        Some(command)
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.current());
+    }
+
+    /// For changes made outside the history, such as salvaging: nothing in
+    /// it matches the journal on disk any more.
+    pub fn mark_unsaved(&mut self) {
+        self.saved = None;
+    }
+
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.current())
+    }
+
+    fn current(&self) -> u64 {
+        self.undo.last().map_or(0, |(serial, _)| *serial)
+    }
+}
+
+/// Brings the dreams in a history written by an older version up to date.
+/// Entries are `[serial, command]` pairs.
+fn upgrade_dreams(history: &mut Value, date_format: &str) -> Result<(), String> {
+    for stack in ["undo", "redo"] {
+        let Some(entries) = history.get_mut(stack).and_then(Value::as_array_mut) else {
+            continue;
+        };
+        for entry in entries {
+            let Some(command) = entry.get_mut(1).and_then(Value::as_object_mut) else {
+                continue;
+            };
+            for field in ["dream", "before", "after"] {
+                if let Some(dream) = command.get_mut(field) {
+                    upgrade_entry(dream, date_format)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The undo history of `journal` is kept as `<journal>.history.json`.
+pub fn history_path(journal: &Path) -> PathBuf {
+    let mut name = journal.as_os_str().to_owned();
+    name.push(".history.json");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::{DreamApp, SortMode},
+        config::Config,
+        constants::DATE_FORMAT,
+        dream::{Intensity, Style},
+        filter::Filter,
+        state::State,
+        store::JsonStore,
+    };
+    use chrono::{FixedOffset, TimeZone};
+
+    fn dream(night: u32, experience: &str) -> Dream {
+        let date = FixedOffset::east_opt(0).unwrap().with_ymd_and_hms(2025, 3, night, 4, 0, 0).unwrap();
+        let mut dream = Dream::new(date, Intensity::Low, Style::Normal);
+        dream.experience = experience.to_string();
+        dream
+    }
+
+    fn open_app(dir: &Path) -> DreamApp {
+        let config = Config {
+            persist_history: true,
+            ..Config::default()
+        };
+        let store = JsonStore::new(dir.join("journal.json"), DATE_FORMAT);
+        DreamApp::new(Box::new(store), config, State::default())
+    }
+
+    fn experiences(app: &DreamApp) -> Vec<&str> {
+        app.dreams.iter().map(|dream| dream.experience.as_str()).collect()
+    }
+
+    #[test]
+    fn undoes_and_redoes_every_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = open_app(dir.path());
+        let first = dream(1, "first");
+        app.add_dream(first.clone());
+        app.add_dream(dream(2, "second"));
+        let mut edited = first.clone();
+        edited.experience = "first, edited".to_string();
+        app.update_dream(edited);
+        app.delete_dream(&app.dreams[1].id.clone());
+        assert_eq!(experiences(&app), ["first, edited"]);
+
+        app.undo().unwrap();
+        assert_eq!(experiences(&app), ["first, edited", "second"]);
+        app.undo().unwrap();
+        assert_eq!(experiences(&app), ["first", "second"]);
+        app.undo().unwrap();
+        assert_eq!(experiences(&app), ["first"]);
+        app.undo().unwrap();
+        assert!(app.dreams.is_empty());
+        app.undo().unwrap();
+        assert_eq!(app.status.as_deref(), Some("Nothing to undo"));
+
+        for _ in 0..4 {
+            app.redo().unwrap();
+        }
+        assert_eq!(experiences(&app), ["first, edited"]);
+        app.redo().unwrap();
+        assert_eq!(app.status.as_deref(), Some("Nothing to redo"));
+    }
+
+    #[test]
+    fn restores_deleted_dream_in_place_when_sorted_and_filtered() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = open_app(dir.path());
+        for (night, experience) in [(3, "a"), (1, "b water"), (2, "c water")] {
+            app.add_dream(dream(night, experience));
+        }
+        app.state.sort = SortMode::DateDesc;
+        app.set_filter(Some("water".parse::<Filter>().unwrap()));
+        let shown: Vec<&str> = app.view.iter().map(|&i| app.dreams[i].experience.as_str()).collect();
+        assert_eq!(shown, ["c water", "b water"]);
+
+        let id = app.dreams[app.view[1]].id;
+        app.delete_dream(&id);
+        app.undo().unwrap();
+
+        assert_eq!(experiences(&app), ["a", "b water", "c water"]);
+        assert_eq!(app.selected_dream().unwrap().id, id);
+    }
+
+    #[test]
+    fn new_command_clears_redo() {
+        let (first, second) = (dream(1, "first"), dream(2, "second"));
+        let mut history = History::default();
+        history.push(Command::Add { dream: first });
+        history.undo().unwrap();
+        history.push(Command::Add { dream: second.clone() });
+
+        assert!(history.redo().is_none());
+        assert_eq!(history.undo().unwrap().dream_id(), second.id);
+        assert!(history.undo().is_none());
+    }
+
+    #[test]
+    fn recognises_saved_state_by_serial() {
+        let mut history = History::default();
+        history.push(Command::Add { dream: dream(1, "first") });
+        history.mark_saved();
+        assert!(history.is_saved());
+
+        // Undoing and adding something else leaves as many commands as when
+        // saved, but not the same ones.
+        history.undo().unwrap();
+        assert!(!history.is_saved());
+        history.push(Command::Add { dream: dream(2, "second") });
+        assert!(!history.is_saved());
+        history.undo().unwrap();
+        assert!(!history.is_saved());
+
+        history.mark_unsaved();
+        history.redo().unwrap();
+        assert!(!history.is_saved());
+    }
+
+    #[test]
+    fn persists_history_next_to_the_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = open_app(dir.path());
+        app.add_dream(dream(1, "first"));
+        app.add_dream(dream(2, "second"));
+        app.save().unwrap();
+        app.undo().unwrap();
+        assert!(app.unsaved_changes());
+        assert!(history_path(&dir.path().join("journal.json")).exists());
+
+        let mut app = open_app(dir.path());
+        assert_eq!(experiences(&app), ["first", "second"]);
+        assert!(!app.unsaved_changes());
+        app.undo().unwrap();
+        assert_eq!(experiences(&app), ["first"]);
+        assert!(app.unsaved_changes());
+        app.redo().unwrap();
+        assert!(!app.unsaved_changes());
+        assert!(app.history.redo().is_none());
+    }
+
+    #[test]
+    fn missing_history_file_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = History::load(&dir.path().join("journal.json"), None, DATE_FORMAT).unwrap();
+        assert!(history.is_saved());
+        assert!(history.undo().is_none());
+    }
+}
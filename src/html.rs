@@ -0,0 +1,503 @@
+use crate::{
+    config::{Color, Config},
+    dream::{Dream, Intensity},
+    stats::{Count, Stats},
+    store::write_atomic,
+};
+use chrono::{Datelike, NaiveDate};
+use ratatui::style::Color as TuiColor;
+use std::{collections::BTreeMap, error::Error, path::Path};
+
+/// Characters of an experience shown under a dream in a list.
+const EXCERPT_LENGTH: usize = 160;
+
+/// Writes the journal as a static site into `dir`: `index.html` by month, a
+/// page per dream under `dreams/`, a page per tag under `tags/`, and
+/// `stats.html`. The same journal and configuration always give the same
+/// files, and pages of dreams or tags no longer in the journal are removed.
+/// Returns the number of pages written.
+pub fn export(journal: &[Dream], config: &Config, dir: &Path) -> Result<usize, Box<dyn Error>> {
+    let cutoff = config.night_cutoff;
+    let mut dreams: Vec<&Dream> = journal.iter().collect();
+    dreams.sort_by_key(|dream| (dream.date, dream.id));
+    let style = stylesheet(config);
+
+    let mut pages: Vec<(String, String)> = Vec::new();
+    for (index, dream) in dreams.iter().enumerate() {
+        let mut body = dream_page(dream, config);
+        body += "<nav class=\"pager\">";
+        if let Some(older) = index.checked_sub(1).and_then(|i| dreams.get(i)) {
+            body += &format!("<a href=\"{}\">← Older</a>", dream_file(older, config));
+        }
+        if let Some(newer) = dreams.get(index + 1) {
+            body += &format!("<a class=\"newer\" href=\"{}\">Newer →</a>", dream_file(newer, config));
+        }
+        body += "</nav>\n";
+        let title = format!("Night of {}", dream.night(cutoff).format("%A %-d %B %Y"));
+        pages.push((
+            format!("dreams/{}", dream_file(dream, config)),
+            page(&title, "../", &style, &body),
+        ));
+    }
+
+    let mut tags: BTreeMap<&str, Vec<&Dream>> = BTreeMap::new();
+    for dream in &dreams {
+        for tag in &dream.tags {
+            tags.entry(tag).or_default().push(dream);
+        }
+    }
+    let mut tag_index = String::from("<h1>Tags</h1>\n<ul class=\"tags\">\n");
+    for (tag, tagged) in &tags {
+        tag_index += &format!(
+            "<li><a href=\"tags/{}\">#{}</a> <span class=\"muted\">{}</span></li>\n",
+            tag_file(tag),
+            escape(tag),
+            tagged.len()
+        );
+        let mut body = format!("<h1>#{}</h1>\n", escape(tag));
+        body += &format!("<p class=\"muted\">{}</p>\n", plural(tagged.len(), "dream"));
+        body += &dream_list(tagged.iter().rev().copied(), config, "../");
+        pages.push((format!("tags/{}", tag_file(tag)), page(&format!("#{}", tag), "../", &style, &body)));
+    }
+    tag_index += "</ul>\n";
+    pages.push(("tags.html".to_string(), page("Tags", "", &style, &tag_index)));
+
+    let months = by_month(&dreams, config);
+    pages.push(("index.html".to_string(), page("Dream journal", "", &style, &index_page(&months, &dreams, config))));
+    pages.push(("stats.html".to_string(), page("Statistics", "", &style, &stats_page(&months, journal, config))));
+
+    for sub in ["dreams", "tags"] {
+        let sub_dir = dir.join(sub);
+        std::fs::create_dir_all(&sub_dir).map_err(|err| format!("{}: {}", sub_dir.display(), err))?;
+        for entry in std::fs::read_dir(&sub_dir)? {
+            let path = entry?.path();
+            let name = format!("{}/{}", sub, path.file_name().unwrap_or_default().to_string_lossy());
+            let stale = path.extension().is_some_and(|ext| ext == "html")
+                && !pages.iter().any(|(page, _)| *page == name);
+            if stale {
+                std::fs::remove_file(&path)?;
+            }
+        }
+    }
+    for (name, html) in &pages {
+        write_atomic(&dir.join(name), html.as_bytes())?;
+    }
+    Ok(pages.len())
+}
+
+/// A complete page: `root` leads from the page back to the top directory.
+fn page(title: &str, root: &str, style: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n<style>\n{style}</style>\n</head>\n<body>\n\
+         <nav class=\"site\"><a href=\"{root}index.html\">Journal</a>\
+         <a href=\"{root}tags.html\">Tags</a><a href=\"{root}stats.html\">Statistics</a></nav>\n\
+         <main>\n{body}</main>\n</body>\n</html>\n",
+        title = escape(title),
+        style = style,
+        root = root,
+        body = body
+    )
+}
+
+/// Months with their dreams, newest first, each newest first.
+fn by_month<'a>(dreams: &[&'a Dream], config: &Config) -> Vec<(NaiveDate, Vec<&'a Dream>)> {
+    let mut months: BTreeMap<NaiveDate, Vec<&Dream>> = BTreeMap::new();
+    for dream in dreams.iter().rev() {
+        let night = dream.night(config.night_cutoff);
+        let month = night.with_day(1).unwrap_or(night);
+        months.entry(month).or_default().push(dream);
+    }
+    months.into_iter().rev().collect()
+}
+
+fn index_page(months: &[(NaiveDate, Vec<&Dream>)], dreams: &[&Dream], config: &Config) -> String {
+    let nights: std::collections::BTreeSet<NaiveDate> =
+        dreams.iter().map(|dream| dream.night(config.night_cutoff)).collect();
+    let mut body = String::from("<h1>Dream journal</h1>\n");
+    body += &format!(
+        "<p class=\"muted\">{} over {}</p>\n",
+        plural(dreams.len(), "dream"),
+        plural(nights.len(), "night")
+    );
+    if !months.is_empty() {
+        body += "<ul class=\"months\">\n";
+        for (month, dreams) in months {
+            body += &format!(
+                "<li><a href=\"#{}\">{}</a> <span class=\"muted\">{}</span></li>\n",
+                month.format("%Y-%m"),
+                month.format("%B %Y"),
+                dreams.len()
+            );
+        }
+        body += "</ul>\n";
+    }
+    for (month, dreams) in months {
+        body += &format!("<h2 id=\"{}\">{}</h2>\n", month.format("%Y-%m"), month.format("%B %Y"));
+        body += &dream_list(dreams.iter().copied(), config, "");
+    }
+    body
+}
+
+/// The dream's fields and its experience, without the page around it.
+fn dream_page(dream: &Dream, config: &Config) -> String {
+    let night = dream.night(config.night_cutoff);
+    let mut body = format!("<h1>Night of {}</h1>\n", night.format("%A %-d %B %Y"));
+    body += &format!(
+        "<p class=\"meta\">{} · {} · {} · Frequency {}</p>\n",
+        escape(&dream.shown_date(&config.date_format)),
+        dream.style,
+        badge(&dream.intensity),
+        dream.frequency
+    );
+    if !dream.tags.is_empty() {
+        let tags: Vec<String> = dream
+            .tags
+            .iter()
+            .map(|tag| format!("<a href=\"../tags/{}\">#{}</a>", tag_file(tag), escape(tag)))
+            .collect();
+        body += &format!("<p class=\"tags\">{}</p>\n", tags.join(" "));
+    }
+    for paragraph in dream.experience.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        let lines: Vec<String> = paragraph.lines().map(escape).collect();
+        body += &format!("<p>{}</p>\n", lines.join("<br>\n"));
+    }
+    body += &format!("<p class=\"muted id\">{}</p>\n", dream.id);
+    body
+}
+
+/// A list of dreams linking to their pages, with the start of each
+/// experience.
+fn dream_list<'a>(dreams: impl Iterator<Item = &'a Dream>, config: &Config, root: &str) -> String {
+    let mut list = String::from("<ul class=\"dreams\">\n");
+    for dream in dreams {
+        let mut excerpt: String = dream.experience.split_whitespace().collect::<Vec<_>>().join(" ");
+        if let Some((at, _)) = excerpt.char_indices().nth(EXCERPT_LENGTH) {
+            excerpt.truncate(at);
+            excerpt += "…";
+        }
+        list += &format!(
+            "<li><a href=\"{}dreams/{}\">{}</a> {} · {}<br><span class=\"muted\">{}</span></li>\n",
+            root,
+            dream_file(dream, config),
+            escape(&dream.shown_date(&config.date_format)),
+            dream.style,
+            badge(&dream.intensity),
+            escape(&excerpt)
+        );
+    }
+    list += "</ul>\n";
+    list
+}
+
+/// Counts that do not depend on today's date, so the page only changes with
+/// the journal.
+fn stats_page(months: &[(NaiveDate, Vec<&Dream>)], dreams: &[Dream], config: &Config) -> String {
+    let last_night = dreams.iter().map(|dream| dream.night(config.night_cutoff)).max();
+    let stats = Stats::new(dreams, config, last_night.unwrap_or_default());
+
+    let mut body = String::from("<h1>Statistics</h1>\n<table class=\"summary\">\n");
+    body += &format!("<tr><th>Dreams</th><td>{}</td></tr>\n", stats.dreams);
+    body += &format!("<tr><th>Nights</th><td>{}</td></tr>\n", stats.nights);
+    if let Some(average) = stats.average_frequency {
+        body += &format!("<tr><th>Average frequency</th><td>{:.1}</td></tr>\n", average);
+    }
+    if let (Some(start), Some(end)) = (stats.streaks.longest_start, stats.streaks.longest_end) {
+        body += &format!(
+            "<tr><th>Longest streak</th><td>{} ({} to {})</td></tr>\n",
+            plural(stats.streaks.longest, "night"),
+            start.format("%-d %b %Y"),
+            end.format("%-d %b %Y")
+        );
+    }
+    body += "</table>\n";
+
+    let per_month: Vec<(String, usize, &str)> = months
+        .iter()
+        .rev()
+        .map(|(month, dreams)| (month.format("%B %Y").to_string(), dreams.len(), ""))
+        .collect();
+    body += &bars("Dreams per month", &per_month);
+    let styles: Vec<(String, usize, &str)> =
+        stats.styles.iter().map(|count| (count.name.clone(), count.count, "")).collect();
+    body += &bars("Style", &styles);
+    let intensities: Vec<(String, usize, &str)> = stats
+        .intensities
+        .iter()
+        .map(|count| (count.name.clone(), count.count, intensity_class(&count.name)))
+        .collect();
+    body += &bars("Intensity", &intensities);
+    body += &counts("Top tags", &stats.top_tags, true);
+    body += &counts("Top words", &stats.top_words, false);
+    body
+}
+
+/// A bar chart as a table, with bars as wide as their share of the largest
+/// count. The third item of each row is an extra class for the bar.
+fn bars(title: &str, rows: &[(String, usize, &str)]) -> String {
+    if rows.is_empty() {
+        return String::new();
+    }
+    let max = rows.iter().map(|(_, count, _)| *count).max().unwrap_or(0).max(1);
+    let mut table = format!("<h2>{}</h2>\n<table class=\"chart\">\n", escape(title));
+    for (name, count, class) in rows {
+        let class = if class.is_empty() { "bar".to_string() } else { format!("bar {}", class) };
+        table += &format!(
+            "<tr><th>{}</th><td><div class=\"{}\" style=\"width: {}%\"></div></td><td>{}</td></tr>\n",
+            escape(name),
+            class,
+            count * 100 / max,
+            count
+        );
+    }
+    table += "</table>\n";
+    table
+}
+
+fn counts(title: &str, counts: &[Count], tags: bool) -> String {
+    if counts.is_empty() {
+        return String::new();
+    }
+    let mut list = format!("<h2>{}</h2>\n<ol class=\"counts\">\n", title);
+    for count in counts {
+        let name = if tags {
+            format!("<a href=\"tags/{}\">#{}</a>", tag_file(&count.name), escape(&count.name))
+        } else {
+            escape(&count.name)
+        };
+        list += &format!("<li>{} <span class=\"muted\">{}</span></li>\n", name, count.count);
+    }
+    list += "</ol>\n";
+    list
+}
+
+fn badge(intensity: &Intensity) -> String {
+    let name = intensity.to_string();
+    format!("<span class=\"badge {}\">{}</span>", intensity_class(&name), name)
+}
+
+fn intensity_class(name: &str) -> &'static str {
+    match name {
+        "Low" => "low",
+        "Medium" => "medium",
+        _ => "high",
+    }
+}
+
+/// Name of a dream's page: its night and short id, as in a per-dream
+/// Markdown export.
+fn dream_file(dream: &Dream, config: &Config) -> String {
+    format!(
+        "{}-{}.html",
+        dream.night(config.night_cutoff).format("%Y-%m-%d"),
+        dream.short_id()
+    )
+}
+
+/// Name of a tag's page. Letters, digits and `-` are kept and every other
+/// byte is written `_xx`, so distinct tags never share a page.
+fn tag_file(tag: &str) -> String {
+    let mut name = String::new();
+    for byte in tag.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' {
+            name.push(byte as char);
+        } else {
+            name += &format!("_{:02x}", byte);
+        }
+    }
+    name + ".html"
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The page style in the colors of the configured theme, with plain black
+/// on white for printing.
+fn stylesheet(config: &Config) -> String {
+    let theme = &config.colors;
+    format!(
+        ":root {{ --background: {}; --panel: {}; --panel-text: {}; --title: {}; --text: {}; \
+         --muted: {}; --highlight: {}; --low: {}; --medium: {}; --high: {}; }}\n\
+         body {{ margin: 0; background: var(--background); color: var(--text); \
+         font: 16px/1.5 system-ui, sans-serif; }}\n\
+         nav.site {{ background: var(--panel); padding: 0.5em 1em; }}\n\
+         nav.site a {{ color: var(--panel-text); font-weight: bold; margin-right: 1.5em; text-decoration: none; }}\n\
+         main {{ max-width: 46em; margin: 0 auto; padding: 1em; }}\n\
+         h1, h2 {{ color: var(--title); font-weight: normal; }}\n\
+         a {{ color: var(--highlight); }}\n\
+         .muted {{ color: var(--muted); }}\n\
+         .id {{ font-size: 0.8em; }}\n\
+         ul.dreams, ul.tags, ul.months {{ list-style: none; padding: 0; }}\n\
+         ul.dreams li {{ margin-bottom: 0.8em; }}\n\
+         ul.months li, ul.tags li {{ display: inline-block; margin-right: 1.2em; }}\n\
+         .badge {{ font-weight: bold; }}\n\
+         .badge.low {{ color: var(--low); }}\n\
+         .badge.medium {{ color: var(--medium); }}\n\
+         .badge.high {{ color: var(--high); }}\n\
+         table {{ border-collapse: collapse; }}\n\
+         th {{ text-align: left; font-weight: normal; padding-right: 1em; white-space: nowrap; }}\n\
+         table.chart {{ width: 100%; }}\n\
+         table.chart td:nth-child(2) {{ width: 100%; }}\n\
+         .bar {{ background: var(--title); height: 0.9em; min-width: 1px; }}\n\
+         .bar.low {{ background: var(--low); }}\n\
+         .bar.medium {{ background: var(--medium); }}\n\
+         .bar.high {{ background: var(--high); }}\n\
+         nav.pager {{ display: flex; margin-top: 2em; }}\n\
+         nav.pager .newer {{ margin-left: auto; }}\n\
+         @media print {{\n\
+         \x20 body {{ background: white; color: black; }}\n\
+         \x20 nav {{ display: none; }}\n\
+         \x20 a, h1, h2, .muted {{ color: black; }}\n\
+         \x20 .bar {{ print-color-adjust: exact; -webkit-print-color-adjust: exact; }}\n\
+         }}\n",
+        css(theme.background),
+        css(theme.panel),
+        css(theme.panel_text),
+        css(theme.title),
+        css(theme.text),
+        css(theme.muted),
+        css(theme.highlight),
+        css(theme.low),
+        css(theme.medium),
+        css(theme.high)
+    )
+}
+
+/// A terminal color as CSS, with the named colors as a typical terminal
+/// palette draws them.
+fn css(color: Color) -> String {
+    let (r, g, b) = match color.0 {
+        TuiColor::Rgb(r, g, b) => (r, g, b),
+        TuiColor::Black | TuiColor::Reset => (0, 0, 0),
+        TuiColor::Red => (205, 49, 49),
+        TuiColor::Green => (13, 188, 121),
+        TuiColor::Yellow => (229, 229, 16),
+        TuiColor::Blue => (36, 114, 200),
+        TuiColor::Magenta => (188, 63, 188),
+        TuiColor::Cyan => (17, 168, 205),
+        TuiColor::Gray => (204, 204, 204),
+        TuiColor::DarkGray => (118, 118, 118),
+        TuiColor::LightRed => (241, 76, 76),
+        TuiColor::LightGreen => (35, 209, 139),
+        TuiColor::LightYellow => (245, 245, 67),
+        TuiColor::LightBlue => (59, 142, 234),
+        TuiColor::LightMagenta => (214, 112, 214),
+        TuiColor::LightCyan => (41, 184, 219),
+        TuiColor::White | TuiColor::Indexed(_) => (229, 229, 229),
+    };
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dates::from_local, dream::Style};
+    use chrono::{Local, NaiveDateTime};
+
+    fn dream(date: &str, experience: &str, tags: &[&str]) -> Dream {
+        let date = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").unwrap();
+        let mut dream = Dream::new(from_local(date).unwrap(), Intensity::Medium, Style::Lucid);
+        dream.experience = experience.to_string();
+        dream.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        dream
+    }
+
+    fn journal() -> Vec<Dream> {
+        vec![
+            dream("2024-11-02 04:00", "Walking through snow.\n\nThen a warm kitchen.", &["snow", "home"]),
+            dream("2024-11-03 05:30", "The snow again.", &["snow"]),
+            dream("2024-12-24 23:10", "A train that never stopped.", &[]),
+        ]
+    }
+
+    /// Every file under `dir` by its path relative to `dir`.
+    fn files(dir: &Path) -> BTreeMap<String, String> {
+        let mut files = BTreeMap::new();
+        for sub in ["", "dreams", "tags"] {
+            for entry in std::fs::read_dir(dir.join(sub)).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_file() {
+                    let name = path.strip_prefix(dir).unwrap().to_string_lossy().into_owned();
+                    files.insert(name, std::fs::read_to_string(&path).unwrap());
+                }
+            }
+        }
+        files
+    }
+
+    #[test]
+    fn exports_the_same_files_every_time() {
+        let (first, second) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let mut journal = journal();
+        let config = Config::default();
+
+        assert_eq!(export(&journal, &config, first.path()).unwrap(), 8);
+        journal.reverse();
+        export(&journal, &config, second.path()).unwrap();
+        let exported = files(first.path());
+        assert_eq!(exported.len(), 8);
+        assert_eq!(exported, files(second.path()));
+
+        export(&journal, &config, first.path()).unwrap();
+        assert_eq!(exported, files(first.path()));
+
+        // Nothing depends on when the export ran.
+        let today = Local::now().date_naive();
+        for html in exported.values() {
+            assert!(!html.contains(&today.format("%-d %B %Y").to_string()));
+            assert!(!html.contains(&today.format("%-d %b %Y").to_string()));
+            assert!(!html.contains(&today.format("%Y-%m-%d").to_string()));
+        }
+        assert!(exported["stats.html"]
+            .contains("<tr><th>Longest streak</th><td>2 nights (1 Nov 2024 to 2 Nov 2024)</td></tr>"));
+    }
+
+    #[test]
+    fn removes_pages_no_longer_in_the_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = journal();
+        let config = Config::default();
+        export(&journal, &config, dir.path()).unwrap();
+        std::fs::write(dir.path().join("dreams/notes.txt"), "kept").unwrap();
+
+        let removed = journal.remove(0);
+        export(&journal, &config, dir.path()).unwrap();
+        let exported = files(dir.path());
+
+        assert!(!exported.contains_key(&format!("dreams/{}", dream_file(&removed, &config))));
+        assert!(!exported.contains_key("tags/home.html"));
+        assert!(exported.contains_key("tags/snow.html"));
+        assert_eq!(exported.keys().filter(|name| name.starts_with("dreams/")).count(), 3);
+        assert_eq!(exported["dreams/notes.txt"], "kept");
+        assert!(!exported["index.html"].contains(&removed.short_id()));
+    }
+
+    #[test]
+    fn escapes_experiences_and_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = [dream(
+            "2024-11-02 04:00",
+            "<script>alert(\"boo\")</script> & more",
+            &["<script>", "a&b"],
+        )];
+        export(&journal, &Config::default(), dir.path()).unwrap();
+        let exported = files(dir.path());
+
+        for html in exported.values() {
+            assert!(!html.contains("<script"));
+        }
+        let page = &exported[&format!("dreams/{}", dream_file(&journal[0], &Config::default()))];
+        assert!(page.contains("&lt;script&gt;alert(&quot;boo&quot;)&lt;/script&gt; &amp; more"));
+        assert!(page.contains("<a href=\"../tags/_3cscript_3e.html\">#&lt;script&gt;</a>"));
+        assert!(exported["tags/a_26b.html"].contains("<title>#a&amp;b</title>"));
+    }
+}
@@ -0,0 +1,520 @@
+use crate::{
+    config::Config,
+    csv_format::CsvImporter,
+    dates::{from_local, parse_when},
+    dream::{normalize_tag, Dream},
+    markdown::MarkdownImporter,
+};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+/// Extensions of the files read from a directory.
+const EXTENSIONS: &[&str] = &["md", "markdown", "txt", "csv", "jsonl", "ndjson"];
+
+/// The fields a record can set, named as in front matter and CSV headers.
+pub const FIELDS: &[&str] = &[
+    "id",
+    "date",
+    "intensity",
+    "style",
+    "frequency",
+    "tags",
+    "experience",
+    "created_at",
+    "updated_at",
+];
+
+/// A record, or why it could not be read.
+pub type Parsed = Result<Record, String>;
+
+/// A dream read from another file as text, before it is checked.
+#[derive(Default)]
+pub struct Record {
+    /// Values by field name; names outside `FIELDS` are ignored.
+    pub fields: BTreeMap<String, String>,
+    pub tags: Vec<String>,
+}
+
+impl Record {
+    /// Checks every field. Only `date` is required: a missing id is
+    /// generated, intensity and style come from the configured defaults, the
+    /// timestamps are now and an empty experience is "N/A".
+    pub fn into_dream(mut self, config: &Config) -> Result<Dream, String> {
+        let mut field = |key: &str| self.fields.remove(key).filter(|value| !value.trim().is_empty());
+
+        let date = field("date").ok_or("no date")?;
+        let date = match DateTime::parse_from_rfc3339(date.trim()) {
+            Ok(date) => date,
+            Err(_) => from_local(parse_when(&date, Local::now().naive_local(), &config.date_format)?)?,
+        };
+        let mut dream = Dream::new(
+            date,
+            config.defaults.intensity.clone(),
+            config.defaults.style.clone(),
+        );
+        if let Some(id) = field("id") {
+            dream.id = Uuid::parse_str(id.trim()).map_err(|_| format!("invalid id '{}'", id))?;
+        }
+        if let Some(intensity) = field("intensity") {
+            dream.intensity = intensity.trim().parse()?;
+        }
+        if let Some(style) = field("style") {
+            dream.style = style.trim().parse()?;
+        }
+        if let Some(frequency) = field("frequency") {
+            dream.frequency = frequency
+                .trim()
+                .parse()
+                .ok()
+                .filter(|frequency| *frequency <= 10)
+                .ok_or_else(|| format!("frequency must be a number from 0 to 10, got '{}'", frequency))?;
+        }
+        for (key, timestamp) in [("created_at", &mut dream.created_at), ("updated_at", &mut dream.updated_at)] {
+            if let Some(value) = field(key) {
+                *timestamp = DateTime::parse_from_rfc3339(value.trim())
+                    .map_err(|_| format!("{} '{}' is not an RFC 3339 date", key, value))?
+                    .with_timezone(&Utc);
+            }
+        }
+        // As in the TUI and `add`, a dream without an experience reads "N/A".
+        dream.experience = field("experience").unwrap_or_else(|| "N/A".to_string());
+        for tag in self.tags.iter().filter_map(|tag| normalize_tag(tag)) {
+            if !dream.tags.contains(&tag) {
+                dream.tags.push(tag);
+            }
+        }
+        Ok(dream)
+    }
+}
+
+/// Reads `--map field=column` options into the CSV columns or JSON keys
+/// fields are read from.
+pub fn parse_mapping(options: &[String]) -> Result<BTreeMap<String, String>, String> {
+    let mut mapping = BTreeMap::new();
+    for option in options {
+        let Some((field, column)) = option.split_once('=') else {
+            return Err(format!("--map '{}' should be field=column", option));
+        };
+        let field = field.trim().to_lowercase();
+        if !FIELDS.contains(&field.as_str()) {
+            return Err(format!(
+                "--map: unknown field '{}'; the fields are {}",
+                field,
+                FIELDS.join(", ")
+            ));
+        }
+        mapping.insert(field, column.trim().to_string());
+    }
+    Ok(mapping)
+}
+
+/// Turns the records of one file format into `Record`s.
+pub trait Importer {
+    /// Every record in `path`, each with where it came from, such as
+    /// `notes.txt line 12`.
+    fn read(&self, path: &Path) -> Result<Vec<(String, Parsed)>, Box<dyn Error>>;
+}
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ImportFormat {
+    /// Markdown with YAML front matter
+    Markdown,
+    /// A header row and a row per dream
+    Csv,
+    /// Plain text with a `## YYYY-MM-DD` header before each dream
+    Text,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl ImportFormat {
+    /// Guessed from the extension, or for other files from whether they
+    /// start with front matter.
+    pub fn detect(path: &Path) -> ImportFormat {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "csv" => ImportFormat::Csv,
+            "jsonl" | "ndjson" => ImportFormat::Jsonl,
+            _ => {
+                let text = std::fs::read_to_string(path).unwrap_or_default();
+                if text.trim_start_matches('\u{feff}').starts_with("---") {
+                    ImportFormat::Markdown
+                } else {
+                    ImportFormat::Text
+                }
+            }
+        }
+    }
+}
+
+/// `mapping` renames fields to the CSV columns or JSON keys they are read
+/// from.
+pub fn importer(format: ImportFormat, mapping: &BTreeMap<String, String>) -> Box<dyn Importer> {
+    match format {
+        ImportFormat::Markdown => Box::new(MarkdownImporter),
+        ImportFormat::Csv => Box::new(CsvImporter {
+            mapping: mapping.clone(),
+        }),
+        ImportFormat::Text => Box::new(TextImporter),
+        ImportFormat::Jsonl => Box::new(JsonLinesImporter {
+            mapping: mapping.clone(),
+        }),
+    }
+}
+
+/// Plain text with a `## YYYY-MM-DD` header before each dream, optionally
+/// followed by a time (`## 2025-01-31 07:30`) or a title, which becomes the
+/// first line of the experience. Text before the first header is ignored.
+pub struct TextImporter;
+
+impl Importer for TextImporter {
+    fn read(&self, path: &Path) -> Result<Vec<(String, Parsed)>, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let mut entries: Vec<(usize, &str, Vec<&str>)> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let header = line
+                .strip_prefix("## ")
+                .map(str::trim)
+                .filter(|header| header.get(..10).is_some_and(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").is_ok()));
+            match (header, entries.last_mut()) {
+                (Some(header), _) => entries.push((number + 1, header, Vec::new())),
+                (None, Some((_, _, body))) => body.push(line),
+                (None, None) => {}
+            }
+        }
+
+        Ok(entries
+            .into_iter()
+            .map(|(number, header, body)| {
+                let (day, rest) = header.split_at(10);
+                let rest = rest.trim();
+                let mut record = Record::default();
+                let mut experience = body.join("\n").trim().to_string();
+                match NaiveTime::parse_from_str(rest, "%H:%M") {
+                    Ok(_) => {
+                        record.fields.insert("date".to_string(), format!("{} {}", day, rest));
+                    }
+                    Err(_) => {
+                        record.fields.insert("date".to_string(), day.to_string());
+                        if !rest.is_empty() {
+                            experience = format!("{}\n{}", rest, experience).trim_end().to_string();
+                        }
+                    }
+                }
+                record.fields.insert("experience".to_string(), experience);
+                (format!("{} line {}", path.display(), number), Ok(record))
+            })
+            .collect())
+    }
+}
+
+/// One JSON object per line with the `FIELDS` as keys, or the keys named in
+/// `mapping`, ignoring case. `tags` is an array or a string of tags.
+pub struct JsonLinesImporter {
+    pub mapping: BTreeMap<String, String>,
+}
+
+impl Importer for JsonLinesImporter {
+    fn read(&self, path: &Path) -> Result<Vec<(String, Parsed)>, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Ok(text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                let source = format!("{} line {}", path.display(), number + 1);
+                (source, self.record(line))
+            })
+            .collect())
+    }
+}
+
+impl JsonLinesImporter {
+    fn record(&self, line: &str) -> Parsed {
+        let value: Value = serde_json::from_str(line).map_err(|err| format!("invalid JSON: {}", err))?;
+        let Value::Object(object) = value else {
+            return Err("expected a JSON object".to_string());
+        };
+        let mut record = Record::default();
+        for field in FIELDS {
+            let key = self.mapping.get(*field).map_or(*field, String::as_str);
+            let Some((_, value)) = object.iter().find(|(name, _)| name.eq_ignore_ascii_case(key)) else {
+                continue;
+            };
+            match (*field, value) {
+                (_, Value::Null) => {}
+                ("tags", Value::Array(tags)) => {
+                    for tag in tags {
+                        match tag {
+                            Value::String(tag) => record.tags.push(tag.clone()),
+                            _ => return Err("tags should be strings".to_string()),
+                        }
+                    }
+                }
+                ("tags", Value::String(tags)) => {
+                    record.tags = tags
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .map(str::to_string)
+                        .collect();
+                }
+                (_, Value::String(text)) => {
+                    record.fields.insert(field.to_string(), text.clone());
+                }
+                (_, Value::Number(number)) => {
+                    record.fields.insert(field.to_string(), number.to_string());
+                }
+                _ => return Err(format!("{} should be text or a number", key)),
+            }
+        }
+        Ok(record)
+    }
+}
+
+/// What importing a record would do.
+pub enum Outcome {
+    New(Dream),
+    /// Already in the journal, or earlier in the import, as the dream with
+    /// this short id.
+    Duplicate(Dream, String),
+    Failed(String),
+}
+
+pub struct Candidate {
+    pub source: String,
+    pub outcome: Outcome,
+}
+
+/// Reads `paths`, with directories replaced by the files inside them, in the
+/// given format or the detected one. Each record is checked against
+/// `existing` and the records before it: a dream is a duplicate if its id is
+/// taken, or if a dream of the same night has the same experience, ignoring
+/// case and spacing.
+pub fn prepare(
+    paths: &[PathBuf],
+    format: Option<ImportFormat>,
+    mapping: &BTreeMap<String, String>,
+    existing: &[Dream],
+    config: &Config,
+) -> Result<Vec<Candidate>, Box<dyn Error>> {
+    let cutoff = config.night_cutoff;
+    let mut ids: HashMap<Uuid, String> = HashMap::new();
+    let mut contents: HashMap<(NaiveDate, String), String> = HashMap::new();
+    for dream in existing {
+        ids.insert(dream.id, dream.short_id());
+        if let Some(key) = content_key(dream, cutoff) {
+            contents.insert(key, dream.short_id());
+        }
+    }
+
+    let mut candidates = Vec::new();
+    for file in files(paths)? {
+        let format = format.unwrap_or_else(|| ImportFormat::detect(&file));
+        for (source, parsed) in importer(format, mapping).read(&file)? {
+            let outcome = match parsed.and_then(|record| record.into_dream(config)) {
+                Err(err) => Outcome::Failed(err),
+                Ok(dream) => {
+                    let key = content_key(&dream, cutoff);
+                    let duplicate = ids
+                        .get(&dream.id)
+                        .or_else(|| key.as_ref().and_then(|key| contents.get(key)))
+                        .cloned();
+                    match duplicate {
+                        Some(of) => Outcome::Duplicate(dream, of),
+                        None => {
+                            ids.insert(dream.id, dream.short_id());
+                            if let Some(key) = key {
+                                contents.insert(key, dream.short_id());
+                            }
+                            Outcome::New(dream)
+                        }
+                    }
+                }
+            };
+            candidates.push(Candidate { source, outcome });
+        }
+    }
+    Ok(candidates)
+}
+
+/// Numbers of new, duplicate and failed candidates.
+pub fn tally(candidates: &[Candidate]) -> (usize, usize, usize) {
+    let count = |matches: fn(&Outcome) -> bool| candidates.iter().filter(|c| matches(&c.outcome)).count();
+    (
+        count(|outcome| matches!(outcome, Outcome::New(_))),
+        count(|outcome| matches!(outcome, Outcome::Duplicate(..))),
+        count(|outcome| matches!(outcome, Outcome::Failed(_))),
+    )
+}
+
+/// `paths` with each directory replaced by the files directly inside it that
+/// have a known extension, in name order.
+fn files(paths: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let known = |path: &Path| {
+        path.extension()
+            .is_some_and(|ext| EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+    };
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(path)
+                .map_err(|err| format!("{}: {}", path.display(), err))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && known(path))
+                .collect();
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+/// The night and the experience with case and spacing ignored; `None` for an
+/// empty experience, which says too little to match on.
+fn content_key(dream: &Dream, cutoff: NaiveTime) -> Option<(NaiveDate, String)> {
+    let text = dream
+        .experience
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    (!text.is_empty()).then(|| (dream.night(cutoff), text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Imports the files in `dir` into `journal` the way the app does,
+    /// returning the candidates.
+    fn import(dir: &Path, journal: &mut Vec<Dream>) -> Vec<Candidate> {
+        let candidates = prepare(&[dir.to_path_buf()], None, &BTreeMap::new(), journal, &Config::default()).unwrap();
+        for candidate in &candidates {
+            if let Outcome::New(dream) = &candidate.outcome {
+                journal.push(dream.clone());
+            }
+        }
+        candidates
+    }
+
+    #[test]
+    fn importing_twice_finds_only_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("notes.txt"),
+            "Dreams from my old notebook.\n\n\
+             ## 2025-03-14 07:30\n\
+             Flying over the harbour.\n\n\
+             ## 2025-03-15 The exam\n\
+             I had not studied.\n\
+             Again.\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("export.jsonl"),
+            r#"{"id": "3a4b5c6d-7e8f-4a1b-9c2d-3e4f5a6b7c8d", "date": "2025-03-16T06:00:00+01:00", "experience": "A house with too many doors.", "tags": ["house"]}
+{"DATE": "2025-03-17 05:45", "Experience": "Late for the train.", "intensity": "High", "frequency": 3, "tags": "travel, late"}
+"#,
+        )
+        .unwrap();
+        let mut journal = Vec::new();
+
+        let first = import(dir.path(), &mut journal);
+        assert_eq!(tally(&first), (4, 0, 0));
+        let sources: Vec<String> = first
+            .iter()
+            .map(|candidate| candidate.source.replace(&dir.path().join("").display().to_string(), ""))
+            .collect();
+        assert_eq!(
+            sources,
+            ["export.jsonl line 1", "export.jsonl line 2", "notes.txt line 3", "notes.txt line 6"]
+        );
+        let experiences: Vec<&str> = journal.iter().map(|dream| dream.experience.as_str()).collect();
+        assert_eq!(
+            experiences,
+            [
+                "A house with too many doors.",
+                "Late for the train.",
+                "Flying over the harbour.",
+                "The exam\nI had not studied.\nAgain."
+            ]
+        );
+        assert_eq!(journal[1].tags, ["travel", "late"]);
+        assert_eq!(journal[1].frequency, 3);
+
+        let second = import(dir.path(), &mut journal);
+        assert_eq!(tally(&second), (0, 4, 0));
+        for (candidate, dream) in second.iter().zip(&journal) {
+            match &candidate.outcome {
+                Outcome::Duplicate(_, of) => assert_eq!(*of, dream.short_id()),
+                _ => panic!("{} was not a duplicate", candidate.source),
+            }
+        }
+    }
+
+    #[test]
+    fn duplicates_within_one_import_are_caught() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("notes.txt"),
+            "## 2025-03-14 07:30\nFlying over the harbour.\n\n## 2025-03-14 08:10\nflying  over the HARBOUR.\n",
+        )
+        .unwrap();
+
+        let candidates = import(dir.path(), &mut Vec::new());
+        assert_eq!(tally(&candidates), (1, 1, 0));
+    }
+
+    #[test]
+    fn reports_records_that_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("export.jsonl"),
+            "{\"experience\": \"No date.\"}\n[1, 2]\n{\"date\": \"2025-03-14\", \"frequency\": 11}\nnot json\n",
+        )
+        .unwrap();
+
+        let errors: Vec<String> = import(dir.path(), &mut Vec::new())
+            .into_iter()
+            .map(|candidate| match candidate.outcome {
+                Outcome::Failed(err) => err,
+                _ => panic!("{} did not fail", candidate.source),
+            })
+            .collect();
+        assert_eq!(errors[0], "no date");
+        assert_eq!(errors[1], "expected a JSON object");
+        assert_eq!(errors[2], "frequency must be a number from 0 to 10, got '11'");
+        assert!(errors[3].starts_with("invalid JSON"));
+    }
+
+    #[test]
+    fn empty_experiences_read_n_a() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("sheet.csv"),
+            "date,experience,tags
+2025-03-14 07:30,,sea
+2025-03-15 07:30,\"  \",\n",
+        )
+        .unwrap();
+
+        let experiences: Vec<String> = import(dir.path(), &mut Vec::new())
+            .into_iter()
+            .map(|candidate| match candidate.outcome {
+                Outcome::New(dream) => dream.experience,
+                _ => panic!("{} was not imported", candidate.source),
+            })
+            .collect();
+        assert_eq!(experiences, ["N/A", "N/A"]);
+    }
+}
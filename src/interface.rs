@@ -1,10 +1,25 @@
-use crate::{app::{DreamApp, InputField, InputMode}, constants::MAX_TRACK, dream::{Intensity, Style}};
+use crate::{
+    app::{DreamApp, InputField, InputMode, SortMode},
+    config::Theme,
+    dates::night_of,
+    dream::{Dream, Intensity, Style},
+    editor::TextEditor,
+    import::{self, Outcome},
+    search::{match_ranges, terms},
+};
 
+use chrono::{Datelike, Days, Local, NaiveDate, Weekday};
+use std::path::Path;
 use ratatui::{
     backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color as TuiColor, Modifier, Style as TuiStyle},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
+    symbols,
+    text::{Span, Spans, Text},
+    widgets::{
+        Axis, BarChart, Block, Borders, Chart, Clear, Dataset, GraphType, LineGauge, List, ListItem,
+        Paragraph, Sparkline,
+    },
     Frame,
 };
 
@@ -19,8 +34,10 @@ pub const STYLE_OPTIONS: &[Style] = &[
 ];
 
 pub fn draw_ui<B: Backend>(f: &mut Frame<B>, app: &mut DreamApp) {
+    let streak = app.streaks().current;
+    let theme = &app.config.colors;
     let size = f.size();
-    let background = Block::default().style(TuiStyle::default().bg(TuiColor::Rgb(0, 0, 50)));
+    let background = Block::default().style(TuiStyle::default().bg(theme.background.0));
     f.render_widget(background, size);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -34,41 +51,68 @@ pub fn draw_ui<B: Backend>(f: &mut Frame<B>, app: &mut DreamApp) {
         )
         .split(size);
 
-    let save_status = if app.unsaved_changes {
-        Paragraph::new("Changes ●")
-            .style(TuiStyle::default().fg(TuiColor::Red))
-            .alignment(Alignment::Right)
+    let mut save_spans = Vec::new();
+    if streak > 0 {
+        save_spans.push(Span::styled(
+            format!("Streak: {} night{}  ", streak, if streak == 1 { "" } else { "s" }),
+            TuiStyle::default().fg(theme.title.0),
+        ));
+    }
+    save_spans.push(if app.unsaved_changes() {
+        Span::styled("Changes ●", TuiStyle::default().fg(theme.unsaved.0))
     } else {
-        Paragraph::new("Up to date ●")
-            .style(TuiStyle::default().fg(TuiColor::Green))
-            .alignment(Alignment::Right)
-    };
+        Span::styled("Up to date ●", TuiStyle::default().fg(theme.saved.0))
+    });
+    let save_status = Paragraph::new(Spans::from(save_spans)).alignment(Alignment::Right);
 
     let logo = Paragraph::new("Dreaming Journal")
         .block(Block::default())
         .style(
             TuiStyle::default()
-                .fg(TuiColor::Cyan)
+                .fg(theme.title.0)
                 .add_modifier(Modifier::BOLD)
                 .add_modifier(Modifier::ITALIC),
         )
         .alignment(Alignment::Left);
 
+    let mut status_parts = Vec::new();
+    if let Some(filter) = &app.filter {
+        status_parts.push(format!("Filter: {} ({} of {})", filter, app.view.len(), app.dreams.len()));
+    }
+    if app.state.sort != SortMode::Added {
+        status_parts.push(format!("Sort: {}", app.state.sort.label()));
+    }
+    if !app.search.is_empty() {
+        status_parts.push(format!("/{} ({} found)", app.search, app.search_hits.len()));
+    }
+    let status_text = app.status.clone().unwrap_or_else(|| status_parts.join("  "));
+    let status = Paragraph::new(status_text)
+        .style(TuiStyle::default().fg(theme.status.0))
+        .alignment(Alignment::Center);
+
     let header = Layout::default()
         .direction(Direction::Horizontal)
         .constraints(
             [
-                Constraint::Percentage(50),
-                Constraint::Percentage(50),
+                Constraint::Percentage(30),
+                Constraint::Percentage(40),
+                Constraint::Percentage(30),
             ]
             .as_ref(),
         )
         .split(chunks[0]);
 
     f.render_widget(logo, header[0]);
-    f.render_widget(save_status, header[1]);
+    f.render_widget(status, header[1]);
+    f.render_widget(save_status, header[2]);
 
-    let days_constraints = vec![Constraint::Percentage(100 / MAX_TRACK as u16); MAX_TRACK];
+    let search_terms = terms(&app.search);
+    let hit_style = TuiStyle::default()
+        .fg(theme.highlight.0)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+
+    let max_track = app.config.max_track;
+    let days_constraints = vec![Constraint::Percentage(100 / max_track as u16); max_track];
 
     let days_chunks = Layout::default()
         .direction(Direction::Horizontal)
@@ -77,26 +121,32 @@ pub fn draw_ui<B: Backend>(f: &mut Frame<B>, app: &mut DreamApp) {
 
     for (i, chunk) in days_chunks.iter().enumerate() {
         let dream_index = app.visible_start + i;
-        let dream = app.dreams.get(dream_index);
+        let dream = app.view.get(dream_index).map(|&i| &app.dreams[i]);
 
         let block = Block::default()
             .borders(Borders::ALL)
             .title(format!("Record {}", dream_index + 1))
-            .style(TuiStyle::default().bg(TuiColor::Rgb(0, 0, 50)));
+            .style(TuiStyle::default().bg(theme.background.0));
 
         if let Some(dream) = dream {
-            let intensity_color = match dream.intensity {
-                Intensity::Low => TuiColor::Green,
-                Intensity::Medium => TuiColor::Yellow,
-                Intensity::High => TuiColor::Red,
-            };
+            let intensity_color = theme.intensity(&dream.intensity);
 
-            let content = format!(
-                "Dreamed at:\n{}\n\nIntensity: {}\nFrequency: {}\nStyle: {}",
-                dream.date, dream.intensity, dream.frequency, dream.style
+            let mut content = format!(
+                "Dreamed at:\n{}\nNight of {}\n\nIntensity: {}\nFrequency: {}\nStyle: {}",
+                dream.shown_date(&app.config.date_format),
+                dream.night(app.config.night_cutoff).format("%a %-d %b"),
+                dream.intensity,
+                dream.frequency,
+                dream.style
             );
+            if !dream.tags.is_empty() {
+                content.push_str(&format!("\nTags: {}", dream.tags.join(", ")));
+            }
+            if let Some(snippet) = snippet(&dream.experience, &search_terms) {
+                content.push_str(&format!("\n\n{}", snippet));
+            }
 
-            let list_item = ListItem::new(content).style(TuiStyle::default().fg(TuiColor::White).fg(intensity_color));
+            let list_item = ListItem::new(highlight(&content, &search_terms, hit_style)).style(TuiStyle::default().fg(TuiColor::White).fg(intensity_color));
 
             let mut state = ratatui::widgets::ListState::default();
             if app.selected == dream_index {
@@ -111,27 +161,54 @@ pub fn draw_ui<B: Backend>(f: &mut Frame<B>, app: &mut DreamApp) {
         } else {
             let empty_paragraph = Paragraph::new("No Dream")
                 .block(block)
-                .style(TuiStyle::default().fg(TuiColor::DarkGray));
+                .style(TuiStyle::default().fg(theme.muted.0));
             f.render_widget(empty_paragraph, *chunk);
         }
     }
 
-    let instructions = Paragraph::new(
-        "Press 'a' to add, 'e' to edit, 'd' to delete, 's' to save, 'q' to quit.\nUse Left/Right to navigate.",
-    )
+    let instructions_text = if app.input_mode == InputMode::Search {
+        format!(
+            "/{}_\n{} found. Enter to keep the search, Esc to cancel.",
+            app.search,
+            app.search_hits.len()
+        )
+    } else if app.input_mode == InputMode::Filter {
+        format!(
+            "Filter: {}_\ne.g. style:nightmare intensity>=medium tag:water after:2025-01-01 freq>5. Enter to apply (empty clears), Esc to cancel.",
+            app.filter_input
+        )
+    } else if app.input_mode == InputMode::Calendar {
+        "Arrows move between nights, PgUp/PgDn change the month and Home goes to tonight.\nEnter shows the dreams of that night, Esc closes the calendar.".to_string()
+    } else if app.input_mode == InputMode::ImportPath {
+        format!(
+            "Import from: {}_\nA Markdown, CSV, dated text or JSON Lines file, or a directory of them. Enter to preview, Esc to cancel.",
+            app.import_input
+        )
+    } else if app.input_mode == InputMode::ImportPreview {
+        "Up/Down to scroll. Enter or 'y' adds the new dreams, which you can then save with 's' or undo with 'u'.\nEsc or 'n' cancels the import.".to_string()
+    } else if app.input_mode == InputMode::Stats {
+        "Statistics cover the whole journal, whatever the filter. The same numbers are printed by 'dreaming stats --json'.\nEsc closes the statistics.".to_string()
+    } else {
+        "Press 'a' to add, 'e' to edit (Ctrl-E in your $EDITOR), 'd' to delete, 's' to save, 't' for tags, 'c' for the calendar, 'S' for statistics, 'i' to import, 'f' to filter, 'o' to sort, '/' to search, 'b' for backups, 'q' to quit.\nUse Left/Right to navigate, n/N for the next/previous match, u/Ctrl-R to undo/redo.".to_string()
+    };
+    let instructions = Paragraph::new(instructions_text)
     .block(
         Block::default()
             .borders(Borders::ALL)
             .title("Instructions")
-            .style(TuiStyle::default().bg(TuiColor::Rgb(100, 216, 230))), 
+            .style(TuiStyle::default().bg(theme.panel.0)), 
     )
-    .style(TuiStyle::default().fg(TuiColor::Black));
+    .style(TuiStyle::default().fg(theme.panel_text.0));
 
     f.render_widget(instructions, chunks[2]);
 
     match app.input_mode {
         InputMode::Editing => {
-            let area = centered_rect(60, 40, size);
+            let area = if matches!(app.input_field, InputField::Date) {
+                centered_rect(60, 60, size)
+            } else {
+                centered_rect(60, 40, size)
+            };
 
             let shadow_area = Rect {
                 x: area.x.saturating_sub(1),
@@ -139,24 +216,88 @@ pub fn draw_ui<B: Backend>(f: &mut Frame<B>, app: &mut DreamApp) {
                 width: area.width + 2,
                 height: area.height + 2,
             };
-            let shadow = Block::default().style(TuiStyle::default().bg(TuiColor::Rgb(0, 0, 40)));
+            let shadow = Block::default().style(TuiStyle::default().bg(theme.shadow.0));
             f.render_widget(shadow, shadow_area);
 
             f.render_widget(Clear, area);
             let input_field_title = match app.input_field {
+                InputField::Date => "When was the dream? (Enter to continue)",
                 InputField::Intensity => "Select the intensity of your dream",
                 InputField::Style => "Select the style",
                 InputField::Frequency => "Set frequency (0-10) (Up/Down)",
-                InputField::Experience => "Describe the experience (F1 to save)",
+                InputField::Tags => "Tags, separated by spaces (Tab to complete, Enter to continue)",
+                InputField::Experience => "Describe the experience (F1 to save, Ctrl-E for $EDITOR, Shift+arrows to select, Ctrl-W deletes a word)",
                 _ => "",
             };
 
             let input_block = Block::default()
                 .borders(Borders::ALL)
                 .title(input_field_title)
-                .style(TuiStyle::default().bg(TuiColor::Rgb(0, 0, 50)));
+                .style(TuiStyle::default().bg(theme.background.0));
 
             match app.input_field {
+                InputField::Date => {
+                    let inner = input_block.inner(area);
+                    f.render_widget(input_block, area);
+                    let date_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(4), Constraint::Min(0)].as_ref())
+                        .split(inner);
+
+                    let choice = app.date_choice();
+                    let (preview, preview_color) = match &choice {
+                        Ok(date) => {
+                            let preview = format!(
+                                "{}, night of {}",
+                                date.format("%A %-d %B %Y, %H:%M"),
+                                night_of(date.naive_local(), app.config.night_cutoff).format("%a %-d %b")
+                            );
+                            (preview, theme.muted.0)
+                        }
+                        Err(err) => (err.clone(), theme.unsaved.0),
+                    };
+                    let input = if app.date_input.is_empty() {
+                        Spans::from(vec![
+                            Span::styled(
+                                app.current_dream.shown_date(&app.config.date_format),
+                                TuiStyle::default().fg(theme.muted.0),
+                            ),
+                            Span::raw("  e.g. yesterday 6am, 2 days ago, mon 7:30"),
+                        ])
+                    } else {
+                        Spans::from(format!("{}_", app.date_input))
+                    };
+                    let input = Paragraph::new(vec![
+                        input,
+                        Spans::from(Span::styled(preview, TuiStyle::default().fg(preview_color))),
+                        Spans::from(Span::styled(
+                            "Left/Right: day, Up/Down: week, PgUp/PgDn: month, Ctrl-U: clear",
+                            TuiStyle::default().fg(theme.muted.0),
+                        )),
+                    ])
+                    .style(TuiStyle::default().fg(theme.text.0));
+                    f.render_widget(input, date_chunks[0]);
+
+                    if let Ok(chosen) = choice {
+                        let (chosen, today) = (chosen.date_naive(), Local::now().date_naive());
+                        let calendar = month_lines(chosen, |day| {
+                            let style = if day == chosen {
+                                TuiStyle::default().add_modifier(Modifier::REVERSED)
+                            } else {
+                                TuiStyle::default()
+                            };
+                            if day == today {
+                                style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+                            } else {
+                                style
+                            }
+                        });
+                        f.render_widget(
+                            Paragraph::new(calendar).style(TuiStyle::default().fg(theme.text.0)),
+                            date_chunks[1],
+                        );
+                    }
+                }
                 InputField::Intensity => {
                     let options: Vec<ListItem> = INTENSITY_OPTIONS
                         .iter()
@@ -166,7 +307,7 @@ pub fn draw_ui<B: Backend>(f: &mut Frame<B>, app: &mut DreamApp) {
                         .highlight_style(
                             TuiStyle::default()
                                 .add_modifier(Modifier::BOLD)
-                                .fg(TuiColor::Gray),
+                                .fg(theme.text.0),
                         )
                         .highlight_symbol(">> ");
 
@@ -188,7 +329,7 @@ pub fn draw_ui<B: Backend>(f: &mut Frame<B>, app: &mut DreamApp) {
                         .highlight_style(
                             TuiStyle::default()
                                 .add_modifier(Modifier::BOLD)
-                                .fg(TuiColor::Gray),
+                                .fg(theme.text.0),
                         )
                         .highlight_symbol(">> ");
 
@@ -206,22 +347,43 @@ pub fn draw_ui<B: Backend>(f: &mut Frame<B>, app: &mut DreamApp) {
                     let frequency_paragraph = Paragraph::new(frequency_display)
                         .block(input_block)
                         .alignment(ratatui::layout::Alignment::Center)
-                        .style(TuiStyle::default().fg(TuiColor::Gray));
+                        .style(TuiStyle::default().fg(theme.text.0));
 
                     f.render_widget(frequency_paragraph, area);
                 }
-                InputField::Experience => {
-                    let input = Paragraph::new(app.input.as_ref())
-                        .style(TuiStyle::default().fg(TuiColor::Gray))
-                        .block(input_block)
-                        .wrap(ratatui::widgets::Wrap { trim: false });
+                InputField::Tags => {
+                    let inner = input_block.inner(area);
+                    f.render_widget(input_block, area);
+                    let tag_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(2), Constraint::Min(0)].as_ref())
+                        .split(inner);
+
+                    let input = Paragraph::new(format!("{}_", app.tag_input))
+                        .style(TuiStyle::default().fg(theme.text.0));
+                    f.render_widget(input, tag_chunks[0]);
 
-                    f.render_widget(input, area);
+                    let suggestions: Vec<ListItem> = app
+                        .tag_suggestions()
+                        .into_iter()
+                        .map(ListItem::new)
+                        .collect();
+                    let suggestions = List::new(suggestions).style(TuiStyle::default().fg(theme.muted.0));
+                    f.render_widget(suggestions, tag_chunks[1]);
+                }
+                InputField::Experience => {
+                    let inner = input_block.inner(area);
+                    f.render_widget(input_block, area);
+                    let selected = TuiStyle::default().add_modifier(Modifier::REVERSED);
+                    draw_editor(f, &mut app.editor, inner, selected);
                 }
                 _ => {}
             }
         }
-        InputMode::ConfirmExport | InputMode::ConfirmDelete | InputMode::ConfirmQuit => {
+        InputMode::ConfirmExport
+        | InputMode::ConfirmDelete
+        | InputMode::ConfirmQuit
+        | InputMode::ConfirmRestore => {
             let area = centered_rect(60, 10, size);
 
             let shadow_area = Rect {
@@ -230,7 +392,7 @@ pub fn draw_ui<B: Backend>(f: &mut Frame<B>, app: &mut DreamApp) {
                 width: area.width + 2,
                 height: area.height + 2,
             };
-            let shadow = Block::default().style(TuiStyle::default().bg(TuiColor::Rgb(0, 0, 40)));
+            let shadow = Block::default().style(TuiStyle::default().bg(theme.shadow.0));
             f.render_widget(shadow, shadow_area);
 
             f.render_widget(Clear, area);
@@ -247,6 +409,14 @@ pub fn draw_ui<B: Backend>(f: &mut Frame<B>, app: &mut DreamApp) {
                     "Confirm Quit",
                     "Are you sure you want to quit? (y/n)",
                 ),
+                InputMode::ConfirmRestore if app.unsaved_changes() => (
+                    "Confirm Restore",
+                    "Restore this backup? Your unsaved changes will be lost. (y/n)",
+                ),
+                InputMode::ConfirmRestore => (
+                    "Confirm Restore",
+                    "Restore this backup? The current journal is backed up first. (y/n)",
+                ),
                 _ => ("", ""),
             };
 
@@ -255,9 +425,9 @@ pub fn draw_ui<B: Backend>(f: &mut Frame<B>, app: &mut DreamApp) {
                     Block::default()
                         .borders(Borders::ALL)
                         .title(title)
-                        .style(TuiStyle::default().bg(TuiColor::Rgb(0, 0, 50))),
+                        .style(TuiStyle::default().bg(theme.background.0)),
                 )
-                .style(TuiStyle::default().fg(TuiColor::Gray));
+                .style(TuiStyle::default().fg(theme.text.0));
 
             f.render_widget(confirm, area);
         }
@@ -270,34 +440,674 @@ pub fn draw_ui<B: Backend>(f: &mut Frame<B>, app: &mut DreamApp) {
                 width: area.width + 2,
                 height: area.height + 2,
             };
-            let shadow = Block::default().style(TuiStyle::default().bg(TuiColor::Rgb(0, 0, 40)));
+            let shadow = Block::default().style(TuiStyle::default().bg(theme.shadow.0));
             f.render_widget(shadow, shadow_area);
 
             f.render_widget(Clear, area);
 
-            if let Some(dream) = app.dreams.get(app.selected) {
+            if let Some(dream) = app.selected_dream() {
                 let content = format!(
-                    "Date: {}\nIntensity: {}\nFrequency: {}\nStyle: {}\nExperience:\n{}",
-                    dream.date, dream.intensity, dream.frequency, dream.style, dream.experience
+                    "Id: {}\nDate: {}\nNight of: {}\nIntensity: {}\nFrequency: {}\nStyle: {}\nTags: {}\nUpdated: {}\nExperience:\n{}",
+                    dream.short_id(),
+                    dream.shown_date(&app.config.date_format),
+                    dream.night(app.config.night_cutoff).format("%A %-d %B %Y"),
+                    dream.intensity,
+                    dream.frequency,
+                    dream.style,
+                    dream.tags.join(", "),
+                    dream.updated_at.with_timezone(&Local).format(&app.config.date_format),
+                    dream.experience
                 );
 
-                let paragraph = Paragraph::new(content)
+                let paragraph = Paragraph::new(highlight(&content, &search_terms, hit_style))
                     .block(
                         Block::default()
                             .borders(Borders::ALL)
                             .title("Dream Details")
-                            .style(TuiStyle::default().bg(TuiColor::Rgb(0, 0, 50))),
+                            .style(TuiStyle::default().bg(theme.background.0)),
                     )
-                    .style(TuiStyle::default().fg(TuiColor::Gray))
+                    .style(TuiStyle::default().fg(theme.text.0))
                     .wrap(ratatui::widgets::Wrap { trim: false });
 
                 f.render_widget(paragraph, area);
             }
         }
+        InputMode::Recovery => {
+            let area = centered_rect(70, 60, size);
+
+            let shadow_area = Rect {
+                x: area.x.saturating_sub(1),
+                y: area.y.saturating_sub(1),
+                width: area.width + 2,
+                height: area.height + 2,
+            };
+            let shadow = Block::default().style(TuiStyle::default().bg(theme.shadow.0));
+            f.render_widget(shadow, shadow_area);
+
+            f.render_widget(Clear, area);
+
+            let recovery_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .split(area);
+
+            let (message, position) = match &app.load_error {
+                Some(err) => (
+                    err.message.as_str(),
+                    err.position
+                        .map(|(line, column)| format!("Line {}, column {}\n", line, column))
+                        .unwrap_or_default(),
+                ),
+                None => ("", String::new()),
+            };
+            let content = format!(
+                "{}{}\n\nThe journal will not be overwritten.\nPress 's' to salvage every entry that still parses, Enter to restore the selected backup, 'q' to quit.",
+                position, message
+            );
+
+            let error_paragraph = Paragraph::new(content)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Journal could not be loaded")
+                        .style(TuiStyle::default().bg(theme.background.0)),
+                )
+                .style(TuiStyle::default().fg(theme.unsaved.0))
+                .wrap(ratatui::widgets::Wrap { trim: false });
+
+            f.render_widget(error_paragraph, recovery_chunks[0]);
+
+            draw_backup_list(f, app, "Backups", recovery_chunks[1]);
+        }
+        InputMode::Backups => {
+            let area = centered_rect(70, 60, size);
+
+            let shadow_area = Rect {
+                x: area.x.saturating_sub(1),
+                y: area.y.saturating_sub(1),
+                width: area.width + 2,
+                height: area.height + 2,
+            };
+            let shadow = Block::default().style(TuiStyle::default().bg(theme.shadow.0));
+            f.render_widget(shadow, shadow_area);
+
+            f.render_widget(Clear, area);
+
+            draw_backup_list(f, app, "Backups (Enter to restore, Esc to close)", area);
+        }
+        InputMode::Tags => {
+            let area = centered_rect(40, 60, size);
+
+            let shadow_area = Rect {
+                x: area.x.saturating_sub(1),
+                y: area.y.saturating_sub(1),
+                width: area.width + 2,
+                height: area.height + 2,
+            };
+            let shadow = Block::default().style(TuiStyle::default().bg(theme.shadow.0));
+            f.render_widget(shadow, shadow_area);
+
+            f.render_widget(Clear, area);
+
+            let mut items = vec![ListItem::new(format!("All dreams ({})", app.dreams.len()))];
+            items.extend(
+                app.tag_counts()
+                    .into_iter()
+                    .map(|(tag, count)| ListItem::new(format!("{} ({})", tag, count))),
+            );
+            let tag_list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Tags (Enter to filter, Esc to close)")
+                        .style(TuiStyle::default().bg(theme.background.0)),
+                )
+                .style(TuiStyle::default().fg(theme.text.0))
+                .highlight_style(
+                    TuiStyle::default()
+                        .add_modifier(Modifier::BOLD)
+                        .fg(theme.text.0),
+                )
+                .highlight_symbol(">> ");
+
+            let mut selection_state = ratatui::widgets::ListState::default();
+            selection_state.select(Some(app.selection_index));
+
+            f.render_stateful_widget(tag_list, area, &mut selection_state);
+        }
+        InputMode::Calendar => {
+            let area = centered_rect(80, 80, size);
+
+            let shadow_area = Rect {
+                x: area.x.saturating_sub(1),
+                y: area.y.saturating_sub(1),
+                width: area.width + 2,
+                height: area.height + 2,
+            };
+            let shadow = Block::default().style(TuiStyle::default().bg(theme.shadow.0));
+            f.render_widget(shadow, shadow_area);
+
+            f.render_widget(Clear, area);
+            draw_calendar(f, app, area);
+        }
+        InputMode::ImportPreview => {
+            let area = centered_rect(80, 70, size);
+
+            let shadow_area = Rect {
+                x: area.x.saturating_sub(1),
+                y: area.y.saturating_sub(1),
+                width: area.width + 2,
+                height: area.height + 2,
+            };
+            let shadow = Block::default().style(TuiStyle::default().bg(theme.shadow.0));
+            f.render_widget(shadow, shadow_area);
+
+            f.render_widget(Clear, area);
+            draw_import_preview(f, app, area);
+        }
+        InputMode::Stats => {
+            let area = centered_rect(90, 90, size);
+
+            let shadow_area = Rect {
+                x: area.x.saturating_sub(1),
+                y: area.y.saturating_sub(1),
+                width: area.width + 2,
+                height: area.height + 2,
+            };
+            let shadow = Block::default().style(TuiStyle::default().bg(theme.shadow.0));
+            f.render_widget(shadow, shadow_area);
+
+            f.render_widget(Clear, area);
+            draw_stats(f, app, area);
+        }
         _ => {}
     }
 }
 
+/// The month around `app.calendar_day` with one cell per night, showing its
+/// number of dreams in the color of the strongest one.
+fn draw_calendar<B: Backend>(f: &mut Frame<B>, app: &DreamApp, area: Rect) {
+    let theme = &app.config.colors;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(app.calendar_day.format("%B %Y").to_string())
+        .style(TuiStyle::default().bg(theme.background.0).fg(theme.text.0));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let first = app.calendar_day.with_day(1).unwrap_or(app.calendar_day);
+    let leading = first.weekday().num_days_from_monday() as usize;
+    let days_in_month = first
+        .iter_days()
+        .take_while(|day| day.month() == first.month())
+        .count();
+    let weeks = (leading + days_in_month).div_ceil(7);
+
+    let mut row_constraints = vec![Constraint::Length(1)];
+    row_constraints.extend((0..weeks).map(|_| Constraint::Ratio(1, weeks as u32)));
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(row_constraints)
+        .split(inner);
+    let columns = |row: Rect| {
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(1, 7); 7].as_ref())
+            .split(row)
+    };
+
+    for (cell, name) in columns(rows[0]).iter().zip(["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]) {
+        let name = Paragraph::new(name).style(TuiStyle::default().fg(theme.muted.0));
+        f.render_widget(name, *cell);
+    }
+
+    let nights = app.nights();
+    let today = app.tonight();
+    for (offset, day) in first.iter_days().take(days_in_month).enumerate() {
+        let position = leading + offset;
+        let cell = columns(rows[1 + position / 7])[position % 7];
+
+        let night = nights.get(&day);
+        let mut number_style = TuiStyle::default().add_modifier(Modifier::BOLD);
+        if night.is_none() {
+            number_style = number_style.fg(theme.muted.0);
+        }
+        if day == today {
+            number_style = number_style.add_modifier(Modifier::UNDERLINED);
+        }
+        let mut lines = vec![Spans::from(Span::styled(format!("{:>2}", day.day()), number_style))];
+        if let Some((count, intensity)) = night {
+            lines.push(Spans::from(Span::styled(
+                format!("{} {}", "●".repeat((*count).min(5)), count),
+                TuiStyle::default().fg(theme.intensity(intensity)),
+            )));
+        }
+
+        let mut style = TuiStyle::default();
+        if day == app.calendar_day {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        f.render_widget(Paragraph::new(lines).style(style), cell);
+    }
+}
+
+/// The records of the previewed import, each marked new, duplicate or
+/// failed.
+fn draw_import_preview<B: Backend>(f: &mut Frame<B>, app: &DreamApp, area: Rect) {
+    let theme = &app.config.colors;
+    let (new, duplicates, failed) = import::tally(&app.import_candidates);
+    let items: Vec<ListItem> = app
+        .import_candidates
+        .iter()
+        .map(|candidate| {
+            let (label, color, detail) = match &candidate.outcome {
+                Outcome::New(dream) => ("new", theme.saved.0, preview_line(dream, &app.config.date_format)),
+                Outcome::Duplicate(dream, of) => (
+                    "duplicate",
+                    theme.muted.0,
+                    format!("as {}: {}", of, preview_line(dream, &app.config.date_format)),
+                ),
+                Outcome::Failed(err) => ("failed", theme.unsaved.0, err.clone()),
+            };
+            ListItem::new(vec![
+                Spans::from(vec![
+                    Span::styled(format!("{:<10}", label), TuiStyle::default().fg(color).add_modifier(Modifier::BOLD)),
+                    Span::styled(candidate.source.clone(), TuiStyle::default().fg(theme.muted.0)),
+                ]),
+                Spans::from(format!("{:<10}{}", "", detail)),
+            ])
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "Import: {} new, {} already in the journal, {} failed",
+                    new, duplicates, failed
+                ))
+                .style(TuiStyle::default().bg(theme.background.0)),
+        )
+        .style(TuiStyle::default().fg(theme.text.0))
+        .highlight_style(TuiStyle::default().add_modifier(Modifier::REVERSED));
+
+    let mut selection_state = ratatui::widgets::ListState::default();
+    selection_state.select(Some(app.selection_index));
+    f.render_stateful_widget(list, area, &mut selection_state);
+}
+
+/// Date, intensity, style and the start of the experience.
+fn preview_line(dream: &Dream, date_format: &str) -> String {
+    let first_line = dream.experience.lines().next().unwrap_or_default();
+    format!(
+        "{}  {}  {}  {}",
+        dream.shown_date(date_format),
+        dream.intensity,
+        dream.style,
+        first_line.chars().take(60).collect::<String>()
+    )
+}
+
+/// Charts of `app.stats`: dreams per week, styles and intensities, recent
+/// nights, average frequency per week and the most common words and tags.
+fn draw_stats<B: Backend>(f: &mut Frame<B>, app: &DreamApp, area: Rect) {
+    let theme = &app.config.colors;
+    let Some(stats) = &app.stats else {
+        return;
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            "Statistics: {} dreams over {} nights",
+            stats.dreams, stats.nights
+        ))
+        .style(TuiStyle::default().bg(theme.background.0).fg(theme.text.0));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let goals_height = if stats.goals.is_empty() { 0 } else { stats.goals.len() as u16 + 2 };
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(goals_height),
+                Constraint::Percentage(35),
+                Constraint::Percentage(30),
+                Constraint::Percentage(35),
+            ]
+            .as_ref(),
+        )
+        .split(inner);
+    let (goals_area, rows) = (rows[0], &rows[1..]);
+    let middle = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage(35),
+                Constraint::Percentage(25),
+                Constraint::Percentage(40),
+            ]
+            .as_ref(),
+        )
+        .split(rows[1]);
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage(50),
+                Constraint::Percentage(25),
+                Constraint::Percentage(25),
+            ]
+            .as_ref(),
+        )
+        .split(rows[2]);
+    let titled = |title: String| Block::default().borders(Borders::ALL).title(title);
+    let bars = TuiStyle::default().fg(theme.title.0);
+    let values = TuiStyle::default().fg(theme.panel_text.0).bg(theme.title.0);
+
+    if !stats.goals.is_empty() {
+        let goals_block = titled("Goals".to_string());
+        let goal_rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Length(1); stats.goals.len()])
+            .split(goals_block.inner(goals_area));
+        f.render_widget(goals_block, goals_area);
+        for (progress, row) in stats.goals.iter().zip(goal_rows.iter()) {
+            let reached = progress.done >= progress.target;
+            let gauge = LineGauge::default()
+                .label(format!(
+                    "{}: {} of {} this {}",
+                    progress.goal, progress.done, progress.target, progress.per
+                ))
+                .ratio((progress.done as f64 / progress.target as f64).min(1.0))
+                .gauge_style(TuiStyle::default().fg(if reached { theme.saved.0 } else { theme.status.0 }));
+            f.render_widget(gauge, *row);
+        }
+    }
+
+    // As many of the latest weeks as fit, seven columns each.
+    let shown_weeks = (rows[0].width.saturating_sub(2) / 7).max(1) as usize;
+    let weeks = &stats.weeks[stats.weeks.len().saturating_sub(shown_weeks)..];
+    let week_labels: Vec<String> = weeks.iter().map(|week| week.start.format("%d %b").to_string()).collect();
+    let week_data: Vec<(&str, u64)> = week_labels
+        .iter()
+        .zip(weeks)
+        .map(|(label, week)| (label.as_str(), week.dreams as u64))
+        .collect();
+    let per_week = BarChart::default()
+        .block(titled("Dreams per week".to_string()))
+        .data(&week_data)
+        .bar_width(6)
+        .bar_gap(1)
+        .bar_style(bars)
+        .value_style(values);
+    f.render_widget(per_week, rows[0]);
+
+    for (title, counts, chunk) in [
+        ("Style", &stats.styles, middle[0]),
+        ("Intensity", &stats.intensities, middle[1]),
+    ] {
+        let data: Vec<(&str, u64)> = counts
+            .iter()
+            .map(|count| (count.name.as_str(), count.count as u64))
+            .collect();
+        let width = (chunk.width.saturating_sub(2) / counts.len().max(1) as u16).saturating_sub(1).max(1);
+        let chart = BarChart::default()
+            .block(titled(title.to_string()))
+            .data(&data)
+            .bar_width(width)
+            .bar_gap(1)
+            .bar_style(bars)
+            .value_style(values);
+        f.render_widget(chart, chunk);
+    }
+
+    // One column per night, up to tonight.
+    let nights = app.nights();
+    let tonight = app.tonight();
+    let shown_nights = middle[2].width.saturating_sub(2) as u64;
+    let recent: Vec<u64> = (0..shown_nights)
+        .rev()
+        .filter_map(|back| tonight.checked_sub_days(Days::new(back)))
+        .map(|night| nights.get(&night).map_or(0, |(count, _)| *count as u64))
+        .collect();
+    let sparkline = Sparkline::default()
+        .block(titled(format!(
+            "Nights: streak {}, longest {}",
+            stats.streaks.current, stats.streaks.longest
+        )))
+        .data(&recent)
+        .style(TuiStyle::default().fg(theme.saved.0));
+    f.render_widget(sparkline, middle[2]);
+
+    let points: Vec<(f64, f64)> = stats
+        .weeks
+        .iter()
+        .enumerate()
+        .filter_map(|(index, week)| week.average_frequency.map(|average| (index as f64, average)))
+        .collect();
+    let last_week = stats.weeks.len().saturating_sub(1).max(1) as f64;
+    let muted = TuiStyle::default().fg(theme.muted.0);
+    let week_label = |index: usize| {
+        let label = stats
+            .weeks
+            .get(index)
+            .map(|week| week.start.format("%d %b %Y").to_string())
+            .unwrap_or_default();
+        Span::styled(label, muted)
+    };
+    let frequency = Chart::new(vec![Dataset::default()
+        .marker(symbols::Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(TuiStyle::default().fg(theme.highlight.0))
+        .data(&points)])
+    .block(titled("Average frequency per week".to_string()))
+    .x_axis(
+        Axis::default()
+            .bounds([0.0, last_week])
+            .labels(vec![week_label(0), week_label(stats.weeks.len().saturating_sub(1))]),
+    )
+    .y_axis(
+        Axis::default()
+            .bounds([0.0, 10.0])
+            .labels(vec![Span::styled("0", muted), Span::styled("5", muted), Span::styled("10", muted)]),
+    );
+    f.render_widget(frequency, bottom[0]);
+
+    for (title, counts, chunk) in [
+        ("Top tags", &stats.top_tags, bottom[1]),
+        ("Top words", &stats.top_words, bottom[2]),
+    ] {
+        let lines: Vec<Spans> = if counts.is_empty() {
+            vec![Spans::from(Span::styled("None yet", muted))]
+        } else {
+            counts
+                .iter()
+                .map(|count| Spans::from(format!("{:>3}  {}", count.count, count.name)))
+                .collect()
+        };
+        f.render_widget(Paragraph::new(lines).block(titled(title.to_string())), chunk);
+    }
+}
+
+/// A month calendar around `month_of`: its name, the weekdays and one row per
+/// week, with each day of the month styled by `day_style`.
+fn month_lines(month_of: NaiveDate, day_style: impl Fn(NaiveDate) -> TuiStyle) -> Vec<Spans<'static>> {
+    let first = month_of.with_day(1).unwrap_or(month_of);
+    let mut lines = vec![
+        Spans::from(Span::styled(
+            format!("{:^20}", first.format("%B %Y").to_string()),
+            TuiStyle::default().add_modifier(Modifier::BOLD),
+        )),
+        Spans::from("Mo Tu We Th Fr Sa Su"),
+    ];
+    let mut week = vec![Span::raw("   ".repeat(first.weekday().num_days_from_monday() as usize))];
+    for day in first.iter_days().take_while(|day| day.month() == first.month()) {
+        week.push(Span::styled(format!("{:>2}", day.day()), day_style(day)));
+        if day.weekday() == Weekday::Sun {
+            lines.push(Spans::from(std::mem::take(&mut week)));
+        } else {
+            week.push(Span::raw(" "));
+        }
+    }
+    if !week.is_empty() {
+        lines.push(Spans::from(week));
+    }
+    lines
+}
+
+/// Draws the wrapped rows of `editor` in view, with the selection styled as
+/// `selected`, and places the terminal cursor.
+fn draw_editor<B: Backend>(f: &mut Frame<B>, editor: &mut TextEditor, area: Rect, selected: TuiStyle) {
+    editor.set_viewport(area.width as usize, area.height as usize);
+    let (rows, cursor) = editor.visible_rows();
+    let selection = editor.selection();
+
+    let lines: Vec<Spans> = rows
+        .iter()
+        .map(|row| {
+            let chars = &editor.line(row.line)[row.columns.clone()];
+            let is_selected = |column: usize| {
+                selection.is_some_and(|(start, end)| {
+                    (row.line, row.columns.start + column) >= start
+                        && (row.line, row.columns.start + column) < end
+                })
+            };
+            let mut spans = Vec::new();
+            let mut start = 0;
+            while start < chars.len() {
+                let highlighted = is_selected(start);
+                let mut end = start + 1;
+                while end < chars.len() && is_selected(end) == highlighted {
+                    end += 1;
+                }
+                let text: String = chars[start..end].iter().collect();
+                spans.push(if highlighted {
+                    Span::styled(text, selected)
+                } else {
+                    Span::raw(text)
+                });
+                start = end;
+            }
+            Spans::from(spans)
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), area);
+
+    if let Some((column, row)) = cursor {
+        f.set_cursor(area.x + column as u16, area.y + row as u16);
+    }
+}
+
+/// `text` with every match of `terms` styled as `hit`.
+fn highlight<'a>(text: &'a str, terms: &[String], hit: TuiStyle) -> Text<'a> {
+    let lines: Vec<Spans> = text
+        .split('\n')
+        .map(|line| {
+            let mut spans = Vec::new();
+            let mut last = 0;
+            for range in match_ranges(line, terms) {
+                spans.push(Span::raw(&line[last..range.start]));
+                spans.push(Span::styled(&line[range.clone()], hit));
+                last = range.end;
+            }
+            spans.push(Span::raw(&line[last..]));
+            Spans::from(spans)
+        })
+        .collect();
+    Text::from(lines)
+}
+
+/// The first line of `experience` with a match, starting shortly before it.
+fn snippet(experience: &str, terms: &[String]) -> Option<String> {
+    experience.lines().find_map(|line| {
+        let first = match_ranges(line, terms).into_iter().next()?;
+        let start = line[..first.start]
+            .char_indices()
+            .rev()
+            .nth(15)
+            .map_or(0, |(i, _)| i);
+        Some(format!("{}{}", if start > 0 { "…" } else { "" }, &line[start..]))
+    })
+}
+
+fn draw_backup_list<B: Backend>(f: &mut Frame<B>, app: &DreamApp, title: &str, area: Rect) {
+    let theme = &app.config.colors;
+    let backup_block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .style(TuiStyle::default().bg(theme.background.0));
+
+    if app.backups.is_empty() {
+        let empty_paragraph = Paragraph::new("No backups found")
+            .block(backup_block)
+            .style(TuiStyle::default().fg(theme.muted.0));
+        f.render_widget(empty_paragraph, area);
+    } else {
+        let items: Vec<ListItem> = app
+            .backups
+            .iter()
+            .map(|path| ListItem::new(path.display().to_string()))
+            .collect();
+        let backup_list = List::new(items)
+            .block(backup_block)
+            .style(TuiStyle::default().fg(theme.text.0))
+            .highlight_style(
+                TuiStyle::default()
+                    .add_modifier(Modifier::BOLD)
+                    .fg(theme.text.0),
+            )
+            .highlight_symbol(">> ");
+
+        let mut selection_state = ratatui::widgets::ListState::default();
+        selection_state.select(Some(app.selection_index));
+
+        f.render_stateful_widget(backup_list, area, &mut selection_state);
+    }
+}
+
+/// The passphrase prompt of an encrypted journal, shown before it is loaded.
+pub fn draw_unlock<B: Backend>(f: &mut Frame<B>, theme: &Theme, journal: &Path, typed: usize, message: Option<&str>) {
+    let size = f.size();
+    f.render_widget(Block::default().style(TuiStyle::default().bg(theme.background.0)), size);
+    let area = centered_rect(60, 30, size);
+    let shadow_area = Rect {
+        x: area.x.saturating_sub(1),
+        y: area.y.saturating_sub(1),
+        width: area.width + 2,
+        height: area.height + 2,
+    };
+    f.render_widget(Block::default().style(TuiStyle::default().bg(theme.shadow.0)), shadow_area);
+    f.render_widget(Clear, area);
+
+    let mut lines = vec![
+        Spans::from(format!("{} is encrypted.", journal.display())),
+        Spans::from(""),
+        Spans::from(vec![
+            Span::raw("Passphrase: "),
+            Span::styled(format!("{}_", "•".repeat(typed)), TuiStyle::default().fg(theme.highlight.0)),
+        ]),
+        Spans::from(""),
+    ];
+    if let Some(message) = message {
+        lines.push(Spans::from(Span::styled(message.to_string(), TuiStyle::default().fg(theme.status.0))));
+    }
+    lines.push(Spans::from(Span::styled(
+        "Enter to unlock, Esc to quit.",
+        TuiStyle::default().fg(theme.muted.0),
+    )));
+    let prompt = Paragraph::new(lines)
+        .wrap(ratatui::widgets::Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Unlock Journal")
+                .style(TuiStyle::default().bg(theme.background.0)),
+        )
+        .style(TuiStyle::default().fg(theme.text.0));
+    f.render_widget(prompt, area);
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -1,17 +1,41 @@
 mod app;
+mod cli;
+mod config;
 mod constants;
+mod crypt;
+mod csv_format;
+mod dates;
 mod dream;
+mod editor;
+mod filter;
+mod history;
+mod html;
+mod import;
 mod interface;
+mod markdown;
+mod paths;
+mod schema;
+mod search;
+mod state;
+mod stats;
+mod store;
 
-use crate::dream::{Dream, Intensity, Style};
+use crate::{
+    dream::{parse_tags, Dream},
+    editor::TextEditor,
+};
 use app::{DreamApp, InputField, InputMode};
-use constants::{DREAM_FILE, MAX_TRACK, TICK_RATE_DURATION};
+use chrono::{Days, Months};
+use clap::Parser;
+use cli::{Cli, Command};
+use config::Config;
 use crossterm::{
     event::{self, Event as CEvent, KeyCode, KeyEvent, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use interface::{draw_ui, INTENSITY_OPTIONS, STYLE_OPTIONS};
+use crypt::Key;
+use interface::{draw_ui, draw_unlock, INTENSITY_OPTIONS, STYLE_OPTIONS};
 use ratatui::{
     backend::{Backend, CrosstermBackend},
     Terminal,
@@ -19,17 +43,100 @@ use ratatui::{
 use std::{
     error::Error,
     io::{self},
-    sync::mpsc,
+    path::Path,
+    sync::{mpsc, Arc, Condvar, Mutex},
     thread,
     time::Duration,
 };
+use state::State;
+use store::{list_backups, open_store, DreamStore};
+use zeroize::Zeroizing;
 
 enum Event<I> {
     Input(I),
     Tick,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+/// Lets the main loop stop the input thread from reading the terminal while
+/// another program, such as an external editor, is using it.
+#[derive(Default)]
+struct InputGate {
+    state: Mutex<GateState>,
+    changed: Condvar,
+}
+
+#[derive(Default, PartialEq)]
+enum GateState {
+    #[default]
+    Open,
+    Closing,
+    Closed,
+}
+
+impl InputGate {
+    /// Returns once the input thread has stopped reading.
+    fn pause(&self) {
+        let mut state = self.state.lock().unwrap();
+        *state = GateState::Closing;
+        while *state != GateState::Closed {
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    fn resume(&self) {
+        *self.state.lock().unwrap() = GateState::Open;
+        self.changed.notify_all();
+    }
+
+    /// Called by the input thread before each read; blocks while paused.
+    fn wait_open(&self) {
+        let mut state = self.state.lock().unwrap();
+        if *state == GateState::Closing {
+            *state = GateState::Closed;
+            self.changed.notify_all();
+        }
+        while *state != GateState::Open {
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    if let Some(Command::Config { print_default }) = cli.command {
+        return cli::run_config(print_default);
+    }
+
+    let mut config = Config::load()?;
+    if let Some(store) = cli.store {
+        config.store = store;
+    }
+    if let Some(backups) = cli.backups {
+        config.backups = backups;
+    }
+    let (journal, notice) = paths::resolve_journal(cli.journal.or(config.journal.clone()), config.store)?;
+    let mut store = open_store(config.store, journal, &config.date_format)?;
+    let encrypted = crypt::read_encrypted(store.path())?;
+
+    if let Some(command) = cli.command {
+        if let Some(data) = &encrypted {
+            store.set_key(Some(cli::unlock(data)?))?;
+        }
+        let mut app = DreamApp::new(store, config, State::load());
+        app.status = notice.or(app.status.take());
+        if let Some(notice) = &app.status {
+            eprintln!("{}", notice);
+        }
+        return cli::run(command, app);
+    }
+
     enable_raw_mode()?;
 
     let mut stdout = io::stdout();
@@ -38,8 +145,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut terminal = Terminal::new(backend)?;
     terminal.clear()?;
 
-    let app = DreamApp::new();
-    let res = run_app(&mut terminal, app);
+    let res = start(&mut terminal, store, config, notice, encrypted);
 
     disable_raw_mode()?;
 
@@ -59,14 +165,95 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Unlocks an encrypted journal, then loads it and runs the TUI. Quitting at
+/// the prompt leaves without loading anything.
+fn start<B: Backend>(
+    terminal: &mut Terminal<B>,
+    mut store: Box<dyn DreamStore>,
+    config: Config,
+    notice: Option<String>,
+    encrypted: Option<Vec<u8>>,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(data) = encrypted {
+        let Some(key) = unlock(terminal, &data, &config, store.path())? else {
+            return Ok(());
+        };
+        store.set_key(Some(key))?;
+    }
+    let mut app = DreamApp::new(store, config, State::load());
+    app.status = notice.or(app.status.take());
+    run_app(terminal, app)
+}
+
+/// Asks for the passphrase until it opens `data`; `None` if the user quits.
+fn unlock<B: Backend>(
+    terminal: &mut Terminal<B>,
+    data: &[u8],
+    config: &Config,
+    journal: &Path,
+) -> Result<Option<Key>, Box<dyn Error>> {
+    let mut passphrase = Zeroizing::new(String::new());
+    let mut message = None;
+    loop {
+        let typed = passphrase.chars().count();
+        terminal.draw(|f| draw_unlock(f, &config.colors, journal, typed, message.as_deref()))?;
+        let CEvent::Key(key) = event::read()? else {
+            continue;
+        };
+        match key.code {
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            KeyCode::Enter => {
+                // Deriving the key takes a moment.
+                terminal.draw(|f| draw_unlock(f, &config.colors, journal, typed, Some("Unlocking…")))?;
+                match Key::unlock(data, &passphrase) {
+                    Ok(key) => return Ok(Some(key)),
+                    Err(err) => message = Some(format!("{}; try again", err)),
+                }
+                passphrase.clear();
+            }
+            KeyCode::Backspace => {
+                passphrase.pop();
+            }
+            KeyCode::Char(c) => passphrase.push(c),
+            _ => {}
+        }
+    }
+}
+
+/// Suspends the TUI and pauses the input thread while `text` is edited in
+/// the user's editor, then restores both.
+fn edit_externally<B: Backend>(
+    terminal: &mut Terminal<B>,
+    gate: &InputGate,
+    text: &str,
+    private: bool,
+) -> Result<String, Box<dyn Error>> {
+    gate.pause();
+    let edited = disable_raw_mode()
+        .and_then(|_| execute!(io::stdout(), LeaveAlternateScreen, crossterm::cursor::Show))
+        .map_err(|err| err.into())
+        .and_then(|_| editor::edit_externally(text, private));
+
+    let restored = enable_raw_mode()
+        .and_then(|_| execute!(io::stdout(), EnterAlternateScreen, crossterm::cursor::Hide))
+        .and_then(|_| terminal.clear());
+    gate.resume();
+    restored?;
+    edited
+}
+
 fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
     mut app: DreamApp,
 ) -> Result<(), Box<dyn Error>> {
-    let tick_rate = Duration::from_millis(TICK_RATE_DURATION);
+    let tick_rate = Duration::from_millis(app.config.tick_rate_ms);
     let (tx, rx) = mpsc::channel();
+    let gate = Arc::new(InputGate::default());
 
+    let input_gate = Arc::clone(&gate);
     thread::spawn(move || loop {
+        input_gate.wait_open();
         if event::poll(tick_rate).unwrap() {
             if let CEvent::Key(key) = event::read().unwrap() {
                 tx.send(Event::Input(key)).unwrap();
@@ -86,71 +273,173 @@ fn run_app<B: Backend>(
                     }
                     KeyCode::Char('a') => {
                         app.input_mode = InputMode::Editing;
-                        app.input_field = InputField::Intensity;
-                        app.selection_index = 0;
+                        app.input_field = InputField::Date;
+                        app.date_input.clear();
                         app.frequency_value = 0;
-                        app.input.clear();
-                        app.current_dream = Dream {
-                            date: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-                            intensity: Intensity::Low,
-                            experience: String::new(),
-                            frequency: 0,
-                            style: Style::Lucid,
-                        };
-                        app.editing_index = None;
+                        app.editor = TextEditor::default();
+                        app.tag_input.clear();
+                        app.current_dream = Dream::new(
+                            chrono::Local::now().fixed_offset(),
+                            app.config.defaults.intensity.clone(),
+                            app.config.defaults.style.clone(),
+                        );
+                        app.selection_index = INTENSITY_OPTIONS
+                            .iter()
+                            .position(|i| *i == app.current_dream.intensity)
+                            .unwrap_or(0);
+                        app.editing_id = None;
                     }
-                    KeyCode::Char('d') => {
-                        if !app.dreams.is_empty() {
-                            app.input_mode = InputMode::ConfirmDelete;
-                        }
+                    KeyCode::Char('d') if !app.view.is_empty() => {
+                        app.input_mode = InputMode::ConfirmDelete;
                     }
                     KeyCode::Char('s') => {
                         app.input_mode = InputMode::ConfirmExport;
                     }
-                    KeyCode::Char('e') => {
-                        if !app.dreams.is_empty() {
-                            app.input_mode = InputMode::Editing;
-                            app.input_field = InputField::Intensity;
-                            app.current_dream = app.dreams[app.selected].clone();
-                            app.editing_index = Some(app.selected);
-                            app.selection_index = INTENSITY_OPTIONS
-                                .iter()
-                                .position(|i| *i == app.current_dream.intensity)
-                                .unwrap_or(0);
-                            app.frequency_value = app.current_dream.frequency;
-                            app.input = app.current_dream.experience.clone();
+                    KeyCode::Char('t') => {
+                        let current = app.filter.as_ref().map(|f| f.to_string());
+                        app.selection_index = app
+                            .tag_counts()
+                            .iter()
+                            .position(|(tag, _)| current == Some(format!("tag:{}", tag)))
+                            .map_or(0, |i| i + 1);
+                        app.input_mode = InputMode::Tags;
+                    }
+                    KeyCode::Char('/') => {
+                        app.clear_search();
+                        app.status = None;
+                        app.input_mode = InputMode::Search;
+                    }
+                    KeyCode::Char('f') => {
+                        app.filter_input = app.filter.as_ref().map(|f| f.to_string()).unwrap_or_default();
+                        app.status = None;
+                        app.input_mode = InputMode::Filter;
+                    }
+                    KeyCode::Char('o') => {
+                        app.status = app
+                            .cycle_sort()
+                            .err()
+                            .map(|err| format!("Could not remember the sort order: {}", err));
+                    }
+                    KeyCode::Char('u') => {
+                        if let Err(err) = app.undo() {
+                            app.status = Some(format!("Undo failed: {}", err));
                         }
                     }
-                    KeyCode::Right => {
-                        if app.selected < app.dreams.len().saturating_sub(1) {
-                            app.selected += 1;
-                            if app.selected >= app.visible_start + MAX_TRACK {
-                                app.visible_start += 1;
-                            }
+                    KeyCode::Char('r') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                        if let Err(err) = app.redo() {
+                            app.status = Some(format!("Redo failed: {}", err));
                         }
                     }
-                    KeyCode::Left => {
-                        if app.selected > 0 {
-                            app.selected -= 1;
-                            if app.selected < app.visible_start {
-                                app.visible_start = app.visible_start.saturating_sub(1);
+                    KeyCode::Char('n') => {
+                        app.jump_to_hit(true);
+                    }
+                    KeyCode::Char('N') => {
+                        app.jump_to_hit(false);
+                    }
+                    KeyCode::Esc => {
+                        app.clear_search();
+                    }
+                    KeyCode::Char('c') if event.modifiers == KeyModifiers::NONE => {
+                        let cutoff = app.config.night_cutoff;
+                        app.calendar_day = app
+                            .selected_dream()
+                            .map(|dream| dream.night(cutoff))
+                            .unwrap_or_else(|| app.tonight());
+                        app.input_mode = InputMode::Calendar;
+                    }
+                    KeyCode::Char('i') => {
+                        app.import_input.clear();
+                        app.status = None;
+                        app.input_mode = InputMode::ImportPath;
+                    }
+                    KeyCode::Char('S') => {
+                        app.stats = Some(app.stats());
+                        app.input_mode = InputMode::Stats;
+                    }
+                    KeyCode::Char('b') => {
+                        app.backups = list_backups(app.store.path());
+                        app.selection_index = 0;
+                        app.input_mode = InputMode::Backups;
+                    }
+                    KeyCode::Char('e')
+                        if event.modifiers.contains(KeyModifiers::CONTROL) && !app.view.is_empty() =>
+                    {
+                        let mut dream = app.dreams[app.view[app.selected]].clone();
+                        match edit_externally(terminal, &gate, &dream.experience, app.store.key().is_some()) {
+                            Ok(text) if text != dream.experience => {
+                                dream.experience = if text.trim().is_empty() {
+                                    "N/A".to_string()
+                                } else {
+                                    text
+                                };
+                                app.update_dream(dream);
                             }
+                            Ok(_) => {}
+                            Err(err) => app.status = Some(format!("Editor failed: {}", err)),
                         }
                     }
-                    KeyCode::Enter => {
-                        if !app.dreams.is_empty() {
-                            app.input_mode = InputMode::ViewingDream;
-                        }
+                    KeyCode::Char('e') if !app.view.is_empty() => {
+                        app.input_mode = InputMode::Editing;
+                        app.input_field = InputField::Date;
+                        app.date_input.clear();
+                        app.current_dream = app.dreams[app.view[app.selected]].clone();
+                        app.editing_id = Some(app.current_dream.id);
+                        app.selection_index = INTENSITY_OPTIONS
+                            .iter()
+                            .position(|i| *i == app.current_dream.intensity)
+                            .unwrap_or(0);
+                        app.frequency_value = app.current_dream.frequency;
+                        app.editor = TextEditor::new(&app.current_dream.experience);
+                        app.tag_input = app.current_dream.tags.join(" ");
+                    }
+                    KeyCode::Right if app.selected < app.view.len().saturating_sub(1) => {
+                        app.selected += 1;
+                        app.scroll_to_selected();
+                    }
+                    KeyCode::Left if app.selected > 0 => {
+                        app.selected -= 1;
+                        app.scroll_to_selected();
+                    }
+                    KeyCode::Enter if !app.view.is_empty() => {
+                        app.input_mode = InputMode::ViewingDream;
                     }
                     _ => {}
                 },
                 InputMode::Editing => match app.input_field {
-                    InputField::Intensity | InputField::Style => match event.code {
-                        KeyCode::Up => {
-                            if app.selection_index > 0 {
-                                app.selection_index -= 1;
+                    InputField::Date => match event.code {
+                        // An invalid date is shown under the input and keeps the step open.
+                        KeyCode::Enter => {
+                            if let Ok(date) = app.date_choice() {
+                                app.current_dream.date = date;
+                                app.input_field = InputField::Intensity;
                             }
                         }
+                        KeyCode::Left => app.shift_date(-1, 0),
+                        KeyCode::Right => app.shift_date(1, 0),
+                        KeyCode::Up => app.shift_date(-7, 0),
+                        KeyCode::Down => app.shift_date(7, 0),
+                        KeyCode::PageUp => app.shift_date(0, -1),
+                        KeyCode::PageDown => app.shift_date(0, 1),
+                        KeyCode::Char('u') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                            app.date_input.clear();
+                        }
+                        KeyCode::Char(c) => {
+                            app.date_input.push(c);
+                        }
+                        KeyCode::Backspace => {
+                            app.date_input.pop();
+                        }
+                        KeyCode::Esc => {
+                            app.input_mode = InputMode::Normal;
+                            app.input_field = InputField::None;
+                            app.editing_id = None;
+                        }
+                        _ => {}
+                    },
+                    InputField::Intensity | InputField::Style => match event.code {
+                        KeyCode::Up if app.selection_index > 0 => {
+                            app.selection_index -= 1;
+                        }
                         KeyCode::Down => {
                             let options_len = match app.input_field {
                                 InputField::Intensity => INTENSITY_OPTIONS.len(),
@@ -167,17 +456,14 @@ fn run_app<B: Backend>(
                                     app.current_dream.intensity =
                                         INTENSITY_OPTIONS[app.selection_index].clone();
                                     app.input_field = InputField::Frequency;
-                                    if app.editing_index.is_none() {
+                                    if app.editing_id.is_none() {
                                         app.frequency_value = 0;
                                     }
                                 }
                                 InputField::Style => {
                                     app.current_dream.style =
                                         STYLE_OPTIONS[app.selection_index].clone();
-                                    app.input_field = InputField::Experience;
-                                    if app.editing_index.is_none() {
-                                        app.input.clear();
-                                    }
+                                    app.input_field = InputField::Tags;
                                 }
                                 _ => {}
                             }
@@ -186,30 +472,53 @@ fn run_app<B: Backend>(
                         KeyCode::Esc => {
                             app.input_mode = InputMode::Normal;
                             app.input_field = InputField::None;
-                            app.editing_index = None;
+                            app.editing_id = None;
                         }
                         _ => {}
                     },
                     InputField::Frequency => match event.code {
-                        KeyCode::Up => {
-                            if app.frequency_value < 10 {
-                                app.frequency_value += 1;
-                            }
+                        KeyCode::Up if app.frequency_value < 10 => {
+                            app.frequency_value += 1;
                         }
-                        KeyCode::Down => {
-                            if app.frequency_value > 0 {
-                                app.frequency_value -= 1;
-                            }
+                        KeyCode::Down if app.frequency_value > 0 => {
+                            app.frequency_value -= 1;
                         }
                         KeyCode::Enter => {
                             app.current_dream.frequency = app.frequency_value;
                             app.input_field = InputField::Style;
-                            app.selection_index = 0;
+                            app.selection_index = STYLE_OPTIONS
+                                .iter()
+                                .position(|s| *s == app.current_dream.style)
+                                .unwrap_or(0);
                         }
                         KeyCode::Esc => {
                             app.input_mode = InputMode::Normal;
                             app.input_field = InputField::None;
-                            app.editing_index = None;
+                            app.editing_id = None;
+                        }
+                        _ => {}
+                    },
+                    InputField::Tags => match event.code {
+                        KeyCode::Tab => {
+                            app.complete_tag();
+                        }
+                        KeyCode::Enter => {
+                            app.current_dream.tags = parse_tags(&app.tag_input);
+                            app.input_field = InputField::Experience;
+                            if app.editing_id.is_none() {
+                                app.editor = TextEditor::default();
+                            }
+                        }
+                        KeyCode::Char(c) => {
+                            app.tag_input.push(c);
+                        }
+                        KeyCode::Backspace => {
+                            app.tag_input.pop();
+                        }
+                        KeyCode::Esc => {
+                            app.input_mode = InputMode::Normal;
+                            app.input_field = InputField::None;
+                            app.editing_id = None;
                         }
                         _ => {}
                     },
@@ -220,64 +529,51 @@ fn run_app<B: Backend>(
                             kind: crossterm::event::KeyEventKind::Press,
                             state: crossterm::event::KeyEventState::NONE,
                         } => {
-                            if app.input.trim().is_empty() {
+                            let text = app.editor.text();
+                            if text.trim().is_empty() {
                                 app.current_dream.experience = "N/A".to_string();
                             } else {
-                                app.current_dream.experience = app.input.drain(..).collect();
+                                app.current_dream.experience = text;
                             }
-                            if let Some(index) = app.editing_index {
-                                app.dreams[index] = app.current_dream.clone();
+                            let id = app.current_dream.id;
+                            if app.editing_id.is_some() {
+                                app.update_dream(app.current_dream.clone());
                             } else {
-                                app.dreams.push(app.current_dream.clone());
-                                app.selected = app.dreams.len() - 1;
-                                if app.dreams.len() > MAX_TRACK {
-                                    app.visible_start = app.dreams.len() - MAX_TRACK;
-                                } else {
-                                    app.visible_start = 0;
-                                }
+                                app.add_dream(app.current_dream.clone());
                             }
+                            app.select_dream(&id);
 
                             app.input_mode = InputMode::Normal;
                             app.input_field = InputField::None;
-                            app.editing_index = None;
-                            app.unsaved_changes = true;
-                        }
-                        KeyEvent {
-                            code: KeyCode::Enter,
-                            modifiers: KeyModifiers::NONE,
-                            kind: crossterm::event::KeyEventKind::Press,
-                            state: crossterm::event::KeyEventState::NONE,
-                        } => {
-                            app.input.push('\n');
-                        }
-                        KeyEvent {
-                            code: KeyCode::Char(c),
-                            ..
-                        } => {
-                            app.input.push(c);
-                        }
-                        KeyEvent {
-                            code: KeyCode::Backspace,
-                            ..
-                        } => {
-                            app.input.pop();
+                            app.editing_id = None;
                         }
                         KeyEvent {
                             code: KeyCode::Esc, ..
                         } => {
                             app.input_mode = InputMode::Normal;
                             app.input_field = InputField::None;
-                            app.editing_index = None;
+                            app.editing_id = None;
+                        }
+                        KeyEvent {
+                            code: KeyCode::Char('e'),
+                            modifiers: KeyModifiers::CONTROL,
+                            ..
+                        } => match edit_externally(terminal, &gate, &app.editor.text(), app.store.key().is_some()) {
+                            Ok(text) => app.editor = TextEditor::new(&text),
+                            Err(err) => app.status = Some(format!("Editor failed: {}", err)),
+                        },
+                        _ => {
+                            app.editor.handle_key(event);
                         }
-                        _ => {}
                     },
                     _ => {}
                 },
                 InputMode::ConfirmExport => match event.code {
                     KeyCode::Char('y') => {
-                        export_dreams(&app.dreams)?;
+                        if let Err(err) = app.save() {
+                            app.status = Some(format!("Save failed: {}", err));
+                        }
                         app.input_mode = InputMode::Normal;
-                        app.unsaved_changes = false;
                     }
                     KeyCode::Char('n') | KeyCode::Esc => {
                         app.input_mode = InputMode::Normal;
@@ -286,15 +582,12 @@ fn run_app<B: Backend>(
                 },
                 InputMode::ConfirmDelete => match event.code {
                     KeyCode::Char('y') => {
-                        if !app.dreams.is_empty() {
-                            app.dreams.remove(app.selected);
+                        if let Some(id) = app.selected_dream().map(|d| d.id) {
+                            app.delete_dream(&id);
                             if app.selected > 0 {
                                 app.selected -= 1;
                             }
-                            if app.visible_start > 0 && app.selected < app.visible_start {
-                                app.visible_start -= 1;
-                            }
-                            app.unsaved_changes = true;
+                            app.scroll_to_selected();
                         }
                         app.input_mode = InputMode::Normal;
                     }
@@ -318,14 +611,209 @@ fn run_app<B: Backend>(
                     }
                     _ => {}
                 },
+                InputMode::Backups => match event.code {
+                    KeyCode::Up if app.selection_index > 0 => {
+                        app.selection_index -= 1;
+                    }
+                    KeyCode::Down if app.selection_index + 1 < app.backups.len() => {
+                        app.selection_index += 1;
+                    }
+                    KeyCode::Enter if !app.backups.is_empty() => {
+                        app.input_mode = InputMode::ConfirmRestore;
+                    }
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => {}
+                },
+                InputMode::Search => match event.code {
+                    KeyCode::Char(c) => {
+                        app.search.push(c);
+                        app.update_search();
+                    }
+                    KeyCode::Backspace => {
+                        app.search.pop();
+                        app.update_search();
+                    }
+                    KeyCode::Enter => {
+                        if app.search.trim().is_empty() {
+                            app.clear_search();
+                        }
+                        app.input_mode = InputMode::Normal;
+                    }
+                    KeyCode::Esc => {
+                        app.clear_search();
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => {}
+                },
+                InputMode::Filter => match event.code {
+                    KeyCode::Char(c) => {
+                        app.filter_input.push(c);
+                    }
+                    KeyCode::Backspace => {
+                        app.filter_input.pop();
+                    }
+                    KeyCode::Enter if app.filter_input.trim().is_empty() => {
+                        app.set_filter(None);
+                        app.input_mode = InputMode::Normal;
+                    }
+                    KeyCode::Enter => match app.filter_input.parse() {
+                        Ok(filter) => {
+                            app.set_filter(Some(filter));
+                            app.status = None;
+                            app.input_mode = InputMode::Normal;
+                        }
+                        Err(err) => app.status = Some(err),
+                    },
+                    KeyCode::Esc => {
+                        app.status = None;
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => {}
+                },
+                InputMode::Calendar => match event.code {
+                    KeyCode::Left => app.calendar_day = app.calendar_day - Days::new(1),
+                    KeyCode::Right => app.calendar_day = app.calendar_day + Days::new(1),
+                    KeyCode::Up => app.calendar_day = app.calendar_day - Days::new(7),
+                    KeyCode::Down => app.calendar_day = app.calendar_day + Days::new(7),
+                    KeyCode::PageUp => {
+                        app.calendar_day = app.calendar_day - Months::new(1);
+                    }
+                    KeyCode::PageDown => {
+                        app.calendar_day = app.calendar_day + Months::new(1);
+                    }
+                    KeyCode::Home => {
+                        app.calendar_day = app.tonight();
+                    }
+                    KeyCode::Enter => {
+                        let night = app.calendar_day;
+                        if app.nights().contains_key(&night) {
+                            app.set_filter(format!("date:{}", night.format("%Y-%m-%d")).parse().ok());
+                            app.status = None;
+                            app.input_mode = InputMode::Normal;
+                        } else {
+                            app.status = Some(format!("No dreams on the night of {}", night.format("%a %-d %b %Y")));
+                        }
+                    }
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => {}
+                },
+                InputMode::ImportPath => match event.code {
+                    KeyCode::Char(c) => {
+                        app.import_input.push(c);
+                    }
+                    KeyCode::Backspace => {
+                        app.import_input.pop();
+                    }
+                    KeyCode::Enter if !app.import_input.trim().is_empty() => match app.preview_import() {
+                        Ok(0) => {
+                            app.status = Some(format!("Nothing to import in {}", app.import_input.trim()));
+                        }
+                        Ok(_) => {
+                            app.status = None;
+                            app.input_mode = InputMode::ImportPreview;
+                        }
+                        Err(err) => app.status = Some(err.to_string()),
+                    },
+                    KeyCode::Esc => {
+                        app.status = None;
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => {}
+                },
+                InputMode::ImportPreview => match event.code {
+                    KeyCode::Up if app.selection_index > 0 => {
+                        app.selection_index -= 1;
+                    }
+                    KeyCode::Down if app.selection_index + 1 < app.import_candidates.len() => {
+                        app.selection_index += 1;
+                    }
+                    KeyCode::Enter | KeyCode::Char('y') => {
+                        let imported = app.commit_import();
+                        app.status = Some(format!("Imported {} dreams; press 's' to save", imported));
+                        app.input_mode = InputMode::Normal;
+                    }
+                    KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('q') => {
+                        app.import_candidates.clear();
+                        app.status = Some("Import cancelled".to_string());
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => {}
+                },
+                InputMode::Stats => match event.code {
+                    KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('S') => {
+                        app.stats = None;
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => {}
+                },
+                InputMode::Tags => match event.code {
+                    KeyCode::Up if app.selection_index > 0 => {
+                        app.selection_index -= 1;
+                    }
+                    KeyCode::Down if app.selection_index < app.tag_counts().len() => {
+                        app.selection_index += 1;
+                    }
+                    KeyCode::Enter => {
+                        let filter = app
+                            .selection_index
+                            .checked_sub(1)
+                            .and_then(|i| app.tag_counts().into_iter().nth(i))
+                            .and_then(|(tag, _)| format!("tag:{}", tag).parse().ok());
+                        app.set_filter(filter);
+                        app.status = None;
+                        app.selection_index = 0;
+                        app.input_mode = InputMode::Normal;
+                    }
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        app.selection_index = 0;
+                        app.input_mode = InputMode::Normal;
+                    }
+                    _ => {}
+                },
+                InputMode::ConfirmRestore => match event.code {
+                    KeyCode::Char('y') => {
+                        let backup = app.backups[app.selection_index].clone();
+                        if let Err(err) = app.restore_backup(&backup) {
+                            app.status = Some(format!("Restore failed: {}", err));
+                        }
+                        app.selection_index = 0;
+                        app.input_mode = InputMode::Normal;
+                    }
+                    KeyCode::Char('n') | KeyCode::Esc => {
+                        app.input_mode = InputMode::Backups;
+                    }
+                    _ => {}
+                },
+                InputMode::Recovery => match event.code {
+                    KeyCode::Up if app.selection_index > 0 => {
+                        app.selection_index -= 1;
+                    }
+                    KeyCode::Down if app.selection_index + 1 < app.backups.len() => {
+                        app.selection_index += 1;
+                    }
+                    KeyCode::Enter if !app.backups.is_empty() => {
+                        let backup = app.backups[app.selection_index].clone();
+                        if let Err(err) = app.restore_backup(&backup) {
+                            app.status = Some(format!("Restore failed: {}", err));
+                        }
+                        app.selection_index = 0;
+                    }
+                    KeyCode::Char('s') => {
+                        if let Err(err) = app.salvage() {
+                            app.status = Some(format!("Salvage failed: {}", err));
+                        }
+                    }
+                    KeyCode::Char('q') => {
+                        return Ok(());
+                    }
+                    _ => {}
+                },
             },
             Event::Tick => {}
         }
     }
 }
-
-fn export_dreams(dreams: &Vec<Dream>) -> Result<(), Box<dyn Error>> {
-    let serialized = serde_json::to_string_pretty(dreams)?;
-    std::fs::write(DREAM_FILE, serialized)?;
-    Ok(())
-}
@@ -0,0 +1,297 @@
+use crate::{
+    config::Config,
+    dream::Dream,
+    import::{Importer, Parsed, Record, FIELDS},
+};
+use chrono::{NaiveTime, SecondsFormat};
+use std::{collections::BTreeMap, error::Error, path::Path};
+
+/// A value in the front matter: a scalar, or a list written `[a, b]` or as
+/// `- item` lines.
+enum Value {
+    Scalar(String),
+    List(Vec<String>),
+}
+
+/// One document with a section per night, oldest first.
+pub fn journal_document(dreams: &[Dream], config: &Config) -> String {
+    let mut dreams: Vec<&Dream> = dreams.iter().collect();
+    dreams.sort_by_key(|dream| dream.date);
+
+    let mut document = String::from("# Dream journal\n");
+    let mut night = None;
+    for dream in dreams {
+        let this_night = dream.night(config.night_cutoff);
+        if night != Some(this_night) {
+            document += &format!("\n## Night of {}\n", this_night.format("%A %-d %B %Y"));
+            night = Some(this_night);
+        }
+        document += &format!(
+            "\n### {} · {} · {} intensity\n\nFrequency: {}",
+            dream.local_date().format("%H:%M"),
+            dream.style,
+            dream.intensity,
+            dream.frequency
+        );
+        if !dream.tags.is_empty() {
+            let tags: Vec<String> = dream.tags.iter().map(|tag| format!("#{}", tag)).collect();
+            document += &format!(" · Tags: {}", tags.join(" "));
+        }
+        document += "\n";
+        if !dream.experience.is_empty() {
+            document += &format!("\n{}\n", dream.experience);
+        }
+    }
+    document
+}
+
+/// Name of a dream's file in a per-dream export: its night and short id, so
+/// files sort by night.
+pub fn file_name(dream: &Dream, cutoff: NaiveTime) -> String {
+    format!("{}-{}.md", dream.night(cutoff).format("%Y-%m-%d"), dream.short_id())
+}
+
+/// A dream as Markdown with every field in YAML front matter and the
+/// experience as the body, read back unchanged by `parse_dream_file`.
+pub fn dream_file(dream: &Dream, cutoff: NaiveTime) -> String {
+    let tags: Vec<String> = dream.tags.iter().map(|tag| scalar(tag)).collect();
+    format!(
+        "---\nid: {}\ndate: {}\nnight: {}\nintensity: {}\nstyle: {}\nfrequency: {}\ntags: [{}]\ncreated_at: {}\nupdated_at: {}\n---\n\n{}\n",
+        dream.id,
+        dream.date.to_rfc3339_opts(SecondsFormat::AutoSi, false),
+        dream.night(cutoff).format("%Y-%m-%d"),
+        dream.intensity.to_string().to_lowercase(),
+        dream.style.to_string().to_lowercase(),
+        dream.frequency,
+        tags.join(", "),
+        dream.created_at.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        dream.updated_at.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        dream.experience
+    )
+}
+
+/// Reads a dream from Markdown with YAML front matter, with the body as the
+/// experience. Keys other than the `import::FIELDS`, such as `night`, are
+/// ignored.
+pub fn parse_dream_file(text: &str) -> Parsed {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let Some(rest) = strip_line(text, "---") else {
+        return Err("no front matter; the file should start with a '---' line".to_string());
+    };
+    let mut front = Vec::new();
+    let mut body = None;
+    let mut remaining = rest;
+    while !remaining.is_empty() {
+        let (line, next) = remaining.split_once('\n').unwrap_or((remaining, ""));
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line == "---" || line == "..." {
+            body = Some(next);
+            break;
+        }
+        front.push(line);
+        remaining = next;
+    }
+    let Some(body) = body else {
+        return Err("the front matter is not closed with a '---' line".to_string());
+    };
+
+    let mut record = Record::default();
+    for (key, value) in parse_front_matter(&front)? {
+        match (key.as_str(), value) {
+            ("tags", Value::List(tags)) => record.tags = tags,
+            ("tags", Value::Scalar(tags)) => {
+                record.tags = tags.split([',', ' ']).map(str::to_string).collect();
+            }
+            (key, Value::List(items)) if FIELDS.contains(&key) && !items.is_empty() => {
+                return Err(format!("{} should be a single value, not a list", key));
+            }
+            (_, Value::Scalar(value)) => {
+                record.fields.insert(key, value);
+            }
+            (_, Value::List(_)) => {}
+        }
+    }
+
+    let body = strip_line(body, "").unwrap_or(body);
+    let body = body
+        .strip_suffix("\r\n")
+        .or_else(|| body.strip_suffix('\n'))
+        .unwrap_or(body);
+    record.fields.insert("experience".to_string(), body.to_string());
+    Ok(record)
+}
+
+/// Reads one dream per file, written by `dream_file` or by hand.
+pub struct MarkdownImporter;
+
+impl Importer for MarkdownImporter {
+    fn read(&self, path: &Path) -> Result<Vec<(String, Parsed)>, Box<dyn Error>> {
+        let parsed = std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| parse_dream_file(&text));
+        Ok(vec![(path.display().to_string(), parsed)])
+    }
+}
+
+/// `text` after a first line equal to `line`.
+fn strip_line<'a>(text: &'a str, line: &str) -> Option<&'a str> {
+    let rest = text.strip_prefix(line)?;
+    rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix('\n'))
+}
+
+/// The `key: value` pairs of the front matter. Covers the YAML written by
+/// note-taking apps: plain or quoted scalars, lists written `[a, b]` or as
+/// `- item` lines, and `#` comments.
+fn parse_front_matter(lines: &[&str]) -> Result<BTreeMap<String, Value>, String> {
+    let mut fields = BTreeMap::new();
+    let mut list_key: Option<String> = None;
+    for (number, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let invalid = || format!("front matter line {}: cannot read '{}'", number + 2, trimmed);
+        if let Some(item) = trimmed.strip_prefix("- ").or((trimmed == "-").then_some("")) {
+            let Some(Value::List(items)) = list_key.as_ref().and_then(|key| fields.get_mut(key)) else {
+                return Err(invalid());
+            };
+            items.push(unquote(item).ok_or_else(invalid)?);
+            continue;
+        }
+        let (key, value) = trimmed.split_once(':').ok_or_else(invalid)?;
+        let (key, value) = (key.trim().to_string(), value.trim());
+        list_key = None;
+        if value.is_empty() {
+            fields.insert(key.clone(), Value::List(Vec::new()));
+            list_key = Some(key);
+        } else if let Some(items) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            let items = split_flow_list(items)
+                .into_iter()
+                .map(unquote)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(invalid)?;
+            fields.insert(key, Value::List(items));
+        } else {
+            fields.insert(key, Value::Scalar(unquote(value).ok_or_else(invalid)?));
+        }
+    }
+    Ok(fields)
+}
+
+/// The items of a `[a, "b, c"]` list, without the brackets.
+fn split_flow_list(items: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut start, mut quote, mut escaped) = (0, None, false);
+    for (at, c) in items.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == ',' => {
+                parts.push(items[start..at].trim());
+                start = at + 1;
+            }
+            None => {}
+        }
+    }
+    parts.push(items[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// A scalar without its quotes, or up to a ` #` comment if unquoted.
+fn unquote(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.starts_with('"') {
+        serde_json::from_str(value).ok()
+    } else if let Some(inner) = value.strip_prefix('\'') {
+        Some(inner.strip_suffix('\'')?.replace("''", "'"))
+    } else {
+        let value = value.split_once(" #").map_or(value, |(value, _)| value);
+        Some(value.trim_end().to_string())
+    }
+}
+
+/// `value` unquoted when YAML would read it back as the same string, and
+/// double-quoted otherwise.
+fn scalar(value: &str) -> String {
+    let plain = value.starts_with(|c: char| c.is_alphabetic())
+        && value.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !["true", "false", "yes", "no", "on", "off", "null"].contains(&value.to_lowercase().as_str());
+    if plain {
+        value.to_string()
+    } else {
+        serde_json::to_string(value).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dream::{Intensity, Style};
+    use chrono::{DateTime, Duration};
+
+    fn read(text: &str) -> Dream {
+        parse_dream_file(text).unwrap().into_dream(&Config::default()).unwrap()
+    }
+
+    #[test]
+    fn dream_file_reads_back_unchanged() {
+        let date = DateTime::parse_from_rfc3339("2025-03-14T02:10:00-05:00").unwrap();
+        let mut dream = Dream::new(date, Intensity::High, Style::Nightmare);
+        dream.frequency = 4;
+        dream.experience = "\nA door opened onto the sea.\n\n---\nThen: \"nothing\".\n".to_string();
+        dream.tags = ["yes", "c++", "a,b", "#hash", "it's", "\"quoted\"", "ünïcode", "null"]
+            .map(str::to_string)
+            .to_vec();
+        dream.created_at = date.with_timezone(&chrono::Utc) + Duration::minutes(5);
+        dream.updated_at = dream.created_at + Duration::days(1);
+
+        let read = read(&dream_file(&dream, Config::default().night_cutoff));
+        assert_eq!(read.id, dream.id);
+        assert_eq!(read.date, dream.date);
+        assert_eq!(read.date.offset(), dream.date.offset());
+        assert!(read.intensity == dream.intensity);
+        assert!(read.style == dream.style);
+        assert_eq!(read.frequency, dream.frequency);
+        assert_eq!(read.experience, dream.experience);
+        assert_eq!(read.tags, dream.tags);
+        assert_eq!(read.created_at, dream.created_at);
+        assert_eq!(read.updated_at, dream.updated_at);
+    }
+
+    #[test]
+    fn reads_hand_written_front_matter() {
+        let dream = read(
+            "---\r\n\
+             # written in a notes app\r\n\
+             date: '2025-03-14T07:30:00+01:00'\r\n\
+             style: Lucid   # flying again\r\n\
+             tags:\r\n\
+             \x20 - flying\r\n\
+             \x20 - \"open sky\"\r\n\
+             aliases: []\r\n\
+             ---\r\n\
+             \r\n\
+             Over the rooftops.\r\n",
+        );
+        assert_eq!(dream.date.to_rfc3339(), "2025-03-14T07:30:00+01:00");
+        assert!(dream.style == Style::Lucid);
+        assert_eq!(dream.tags, ["flying", "open-sky"]);
+        assert_eq!(dream.experience, "Over the rooftops.");
+    }
+
+    #[test]
+    fn explains_unreadable_front_matter() {
+        let error = |text: &str| parse_dream_file(text).err().unwrap();
+        assert_eq!(error("Just a note."), "no front matter; the file should start with a '---' line");
+        assert_eq!(error("---\ndate: 2025-03-14\n"), "the front matter is not closed with a '---' line");
+        assert_eq!(error("---\n- flying\n---\n"), "front matter line 2: cannot read '- flying'");
+        assert_eq!(
+            error("---\nstyle:\n  - lucid\n  - normal\n---\n"),
+            "style should be a single value, not a list"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{
+    constants::{APP_DIR, DREAM_DB, DREAM_FILE},
+    store::{list_backups, StoreKind},
+};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+/// `$XDG_DATA_HOME/dreaming` (or the platform equivalent).
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_DIR))
+}
+
+/// `$XDG_CONFIG_HOME/dreaming` (or the platform equivalent).
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_DIR))
+}
+
+/// `$XDG_STATE_HOME/dreaming`, falling back to the data directory on
+/// platforms without a state directory.
+pub fn state_dir() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_dir)
+        .map(|dir| dir.join(APP_DIR))
+}
+
+/// A directory kept in memory rather than on disk: `$XDG_RUNTIME_DIR`, or
+/// `/dev/shm` where it exists.
+pub fn memory_dir() -> Option<PathBuf> {
+    dirs::runtime_dir().or_else(|| Some(PathBuf::from("/dev/shm")).filter(|dir| dir.is_dir()))
+}
+
+pub fn journal_file_name(kind: StoreKind) -> &'static str {
+    match kind {
+        StoreKind::Json => DREAM_FILE,
+        StoreKind::Sqlite => DREAM_DB,
+    }
+}
+
+/// Expands a leading `~/` to the home directory.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Picks the journal path: an explicit path (flag, env var or config) wins,
+/// otherwise the data directory is used. A journal left in the working
+/// directory by older versions is moved there the first time.
+pub fn resolve_journal(
+    explicit: Option<PathBuf>,
+    kind: StoreKind,
+) -> Result<(PathBuf, Option<String>), Box<dyn Error>> {
+    if let Some(path) = explicit {
+        return Ok((expand_home(&path), None));
+    }
+
+    let file_name = journal_file_name(kind);
+    let Some(dir) = data_dir() else {
+        return Ok((PathBuf::from(file_name), None));
+    };
+    let journal = dir.join(file_name);
+    std::fs::create_dir_all(&dir)?;
+
+    let legacy = PathBuf::from(file_name);
+    if journal.exists() || !legacy.is_file() {
+        return Ok((journal, None));
+    }
+
+    for backup in list_backups(&legacy) {
+        if let Some(name) = backup.file_name() {
+            move_file(&backup, &dir.join(name))?;
+        }
+    }
+    move_file(&legacy, &journal)?;
+    let notice = format!("Moved ./{} to {}", file_name, journal.display());
+    Ok((journal, Some(notice)))
+}
+
+fn move_file(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    if std::fs::rename(from, to).is_err() {
+        std::fs::copy(from, to)?;
+        std::fs::remove_file(from)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,202 @@
+use crate::dream::{legacy_date, legacy_timestamp, unreadable_dates, Dream};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::error::Error;
+use uuid::Uuid;
+
+/// Version of the JSON journal written by this build.
+///
+/// - 0: a bare array of dreams without ids or timestamps
+/// - 1: a bare array of dreams with `id`, `created_at` and `updated_at`
+/// - 2: `{ "schema_version": 2, "dreams": [...] }`
+/// - 3: every dream has a `tags` array
+/// - 4: `date` is RFC 3339 with the UTC offset it was recorded in
+///
+/// Changing the on-disk shape means bumping this and appending a step to
+/// `MIGRATIONS`.
+pub const SCHEMA_VERSION: u32 = 4;
+
+/// Upgrades a journal by one version, reading old dates with the configured
+/// `date_format`.
+type Migration = fn(Value, &str) -> Result<Value, String>;
+
+/// `MIGRATIONS[n]` upgrades a version `n` journal to version `n + 1`.
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] =
+    [add_ids, add_envelope, add_tags, add_offsets];
+
+#[derive(Serialize)]
+struct JournalRef<'a> {
+    schema_version: u32,
+    dreams: &'a [Dream],
+}
+
+#[derive(Deserialize)]
+struct Journal {
+    dreams: Vec<Dream>,
+}
+
+/// A decoded journal and the schema version it was stored in.
+pub struct Decoded {
+    pub dreams: Vec<Dream>,
+    pub version: u32,
+}
+
+pub fn encode(dreams: &[Dream]) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&JournalRef {
+        schema_version: SCHEMA_VERSION,
+        dreams,
+    })
+}
+
+/// Decodes a journal of any known version, migrating it to `SCHEMA_VERSION`.
+pub fn decode(data: &[u8], date_format: &str) -> Result<Decoded, Box<dyn Error>> {
+    let value: Value = serde_json::from_slice(data)?;
+    let version = detect_version(&value)?;
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "journal schema version {} is newer than this build supports ({})",
+            version, SCHEMA_VERSION
+        )
+        .into());
+    }
+    if version == SCHEMA_VERSION {
+        // Decoded from the bytes again so errors keep their line and column.
+        let journal: Journal = serde_json::from_slice(data)?;
+        return Ok(Decoded {
+            dreams: journal.dreams,
+            version,
+        });
+    }
+
+    let mut value = value;
+    for (from, migrate) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        value = migrate(value, date_format)
+            .map_err(|err| format!("upgrading journal from schema version {}: {}", from, err))?;
+    }
+    let journal: Journal = serde_json::from_value(value)?;
+    Ok(Decoded {
+        dreams: journal.dreams,
+        version,
+    })
+}
+
+fn detect_version(value: &Value) -> Result<u32, String> {
+    match value {
+        Value::Array(entries) if !entries.is_empty() && entries.iter().all(|e| e.get("id").is_some()) => {
+            Ok(1)
+        }
+        Value::Array(_) => Ok(0),
+        Value::Object(fields) => fields
+            .get("schema_version")
+            .and_then(Value::as_u64)
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| "the journal has no valid schema_version".to_string()),
+        _ => Err("the journal is neither an object nor an array".to_string()),
+    }
+}
+
+/// 0 → 1: give every entry an id and timestamps.
+fn add_ids(mut value: Value, date_format: &str) -> Result<Value, String> {
+    let entries = value.as_array_mut().ok_or("expected an array of dreams")?;
+    let unreadable: Vec<String> = entries
+        .iter_mut()
+        .filter_map(|entry| add_entry_ids(entry, date_format).err())
+        .collect();
+    if !unreadable.is_empty() {
+        return Err(unreadable_dates(&unreadable, date_format));
+    }
+    Ok(value)
+}
+
+/// 1 → 2: wrap the array in a versioned envelope.
+fn add_envelope(value: Value, _: &str) -> Result<Value, String> {
+    if !value.is_array() {
+        return Err("expected an array of dreams".to_string());
+    }
+    Ok(json!({ "schema_version": 2, "dreams": value }))
+}
+
+/// 2 → 3: start every dream with no tags.
+fn add_tags(mut value: Value, _: &str) -> Result<Value, String> {
+    let entries = value
+        .get_mut("dreams")
+        .and_then(Value::as_array_mut)
+        .ok_or("expected a list of dreams")?;
+    for entry in entries {
+        add_entry_tags(entry);
+    }
+    value["schema_version"] = json!(3);
+    Ok(value)
+}
+
+/// 3 → 4: give every date an offset. Dates so far were written in UTC.
+fn add_offsets(mut value: Value, date_format: &str) -> Result<Value, String> {
+    let entries = value
+        .get_mut("dreams")
+        .and_then(Value::as_array_mut)
+        .ok_or("expected a list of dreams")?;
+    let unreadable: Vec<String> = entries
+        .iter_mut()
+        .filter_map(|entry| add_entry_offset(entry, date_format).err())
+        .collect();
+    if !unreadable.is_empty() {
+        return Err(unreadable_dates(&unreadable, date_format));
+    }
+    value["schema_version"] = json!(4);
+    Ok(value)
+}
+
+/// Brings a single entry from any version up to date, for salvaging entries
+/// out of a journal that no longer parses as a whole.
+pub fn upgrade_entry(entry: &mut Value, date_format: &str) -> Result<(), String> {
+    add_entry_ids(entry, date_format)
+        .and_then(|_| {
+            add_entry_tags(entry);
+            add_entry_offset(entry, date_format)
+        })
+        .map_err(|date| unreadable_dates(&[date], date_format))
+}
+
+/// Rewrites `date` as RFC 3339. An entry whose date cannot be read is left
+/// alone and its date returned.
+fn add_entry_offset(entry: &mut Value, date_format: &str) -> Result<(), String> {
+    let Some(date) = entry.get_mut("date") else {
+        return Ok(());
+    };
+    let Some(text) = date.as_str() else {
+        return Ok(());
+    };
+    let parsed = legacy_date(text, date_format).ok_or_else(|| text.to_string())?;
+    *date = json!(parsed);
+    Ok(())
+}
+
+fn add_entry_tags(entry: &mut Value) {
+    if let Some(fields) = entry.as_object_mut() {
+        fields.entry("tags").or_insert_with(|| json!([]));
+    }
+}
+
+/// Adds the `id`, `created_at` and `updated_at` fields to an entry written
+/// before they existed, leaving every other field untouched. The timestamps
+/// come from `date`; an entry whose date cannot be read is left alone and its
+/// date returned.
+fn add_entry_ids(entry: &mut Value, date_format: &str) -> Result<(), String> {
+    let Some(fields) = entry.as_object_mut() else {
+        return Ok(());
+    };
+    if fields.contains_key("id") {
+        return Ok(());
+    }
+
+    let date = fields.get("date").and_then(|d| d.as_str()).unwrap_or_default();
+    let created_at = legacy_timestamp(date, date_format).ok_or_else(|| date.to_string())?;
+    fields.insert("id".to_string(), json!(Uuid::new_v4()));
+    fields
+        .entry("created_at")
+        .or_insert_with(|| json!(created_at));
+    fields
+        .entry("updated_at")
+        .or_insert_with(|| json!(created_at));
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use crate::dream::Dream;
+use std::{collections::BTreeMap, ops::Range};
+
+/// Word index over the searchable text of every dream. Terms match at the
+/// start of a word, so a query only looks at the dreams with a word starting
+/// with each of its words, then checks those for the whole terms.
+#[derive(Default)]
+pub struct SearchIndex {
+    words: BTreeMap<String, Vec<usize>>,
+    texts: Vec<String>,
+}
+
+impl SearchIndex {
+    pub fn new(dreams: &[Dream], date_format: &str) -> SearchIndex {
+        let mut index = SearchIndex::default();
+        for (i, dream) in dreams.iter().enumerate() {
+            let text = fold(&searchable_text(dream, date_format));
+            for word in words(&text) {
+                let postings = index.words.entry(word.to_string()).or_default();
+                if postings.last() != Some(&i) {
+                    postings.push(i);
+                }
+            }
+            index.texts.push(text);
+        }
+        index
+    }
+
+    /// Indices of the dreams containing every term of `query`, ascending.
+    pub fn search(&self, query: &str) -> Vec<usize> {
+        let terms = terms(query);
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut candidates: Option<Vec<usize>> = None;
+        for word in terms.iter().flat_map(|term| words(term)) {
+            let mut matching: Vec<usize> = self
+                .words
+                .range(word.to_string()..)
+                .take_while(|(indexed, _)| indexed.starts_with(word))
+                .flat_map(|(_, postings)| postings.iter().copied())
+                .collect();
+            matching.sort_unstable();
+            matching.dedup();
+            candidates = Some(match candidates {
+                Some(candidates) => candidates
+                    .into_iter()
+                    .filter(|i| matching.binary_search(i).is_ok())
+                    .collect(),
+                None => matching,
+            });
+        }
+
+        candidates
+            .unwrap_or_else(|| (0..self.texts.len()).collect())
+            .into_iter()
+            .filter(|&i| {
+                terms.iter().all(|term| {
+                    self.texts[i]
+                        .match_indices(term.as_str())
+                        .any(|(start, _)| starts_word(&self.texts[i], start, term))
+                })
+            })
+            .collect()
+    }
+}
+
+/// Experience, tags and the fields shown on a card, with the date as shown.
+pub fn searchable_text(dream: &Dream, date_format: &str) -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        dream.shown_date(date_format),
+        dream.intensity,
+        dream.style,
+        dream.tags.join(" "),
+        dream.experience
+    )
+}
+
+/// The lowercased, whitespace-separated terms of a query.
+pub fn terms(query: &str) -> Vec<String> {
+    query.split_whitespace().map(fold).collect()
+}
+
+/// Byte ranges of `text` matching any of `terms`, in order and merged.
+pub fn match_ranges(text: &str, terms: &[String]) -> Vec<Range<usize>> {
+    let chars: Vec<(usize, char)> = text.char_indices().map(|(i, c)| (i, fold_char(c))).collect();
+    let byte_at = |i: usize| chars.get(i).map_or(text.len(), |(byte, _)| *byte);
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for term in terms {
+        let term: Vec<char> = term.chars().collect();
+        if term.is_empty() || term.len() > chars.len() {
+            continue;
+        }
+        for start in 0..=chars.len() - term.len() {
+            let at_word_start = start == 0
+                || !term[0].is_alphanumeric()
+                || !chars[start - 1].1.is_alphanumeric();
+            if at_word_start
+                && chars[start..start + term.len()].iter().map(|(_, c)| *c).eq(term.iter().copied())
+            {
+                ranges.push(byte_at(start)..byte_at(start + term.len()));
+            }
+        }
+    }
+
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Whether a match of `term` at byte `start` of `text` begins a word.
+fn starts_word(text: &str, start: usize, term: &str) -> bool {
+    !term.starts_with(char::is_alphanumeric)
+        || !text[..start].ends_with(char::is_alphanumeric)
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+}
+
+/// Lowercases one character at a time, so character positions are kept.
+fn fold(text: &str) -> String {
+    text.chars().map(fold_char).collect()
+}
+
+fn fold_char(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dates::from_local,
+        dream::{Intensity, Style},
+    };
+    use chrono::NaiveDateTime;
+
+    fn dream(when: &str, intensity: Intensity, style: Style, tags: &[&str], experience: &str) -> Dream {
+        let date = from_local(NaiveDateTime::parse_from_str(when, "%Y-%m-%d %H:%M").unwrap()).unwrap();
+        let mut dream = Dream::new(date, intensity, style);
+        dream.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        dream.experience = experience.to_string();
+        dream
+    }
+
+    fn index() -> SearchIndex {
+        let dreams = [
+            dream("2025-06-01 04:00", Intensity::Low, Style::Lucid, &["sea"], "Flying over the harbour"),
+            dream("2025-06-02 04:00", Intensity::High, Style::Nightmare, &["dentist"], "A nightmare about teeth"),
+            dream("2025-07-01 04:00", Intensity::Medium, Style::Normal, &["sea-life"], "Ünderwater city"),
+        ];
+        SearchIndex::new(&dreams, "%Y-%m-%d %H:%M")
+    }
+
+    #[test]
+    fn matches_word_prefixes_in_every_field() {
+        let index = index();
+        assert_eq!(index.search("fly"), [0]);
+        assert_eq!(index.search("ying"), Vec::<usize>::new());
+        // Tags, the style, the intensity and the date as shown.
+        assert_eq!(index.search("sea"), [0, 2]);
+        assert_eq!(index.search("dent"), [1]);
+        assert_eq!(index.search("night"), [1]);
+        assert_eq!(index.search("lucid"), [0]);
+        assert_eq!(index.search("HIGH"), [1]);
+        assert_eq!(index.search("2025-06"), [0, 1]);
+        assert_eq!(index.search("üNDER"), [2]);
+    }
+
+    #[test]
+    fn needs_every_term_of_the_query() {
+        let index = index();
+        assert_eq!(index.search("sea fly"), [0]);
+        assert_eq!(index.search("city  sea"), [2]);
+        assert_eq!(index.search("sea teeth"), Vec::<usize>::new());
+        assert_eq!(index.search("sea-life"), [2]);
+        assert_eq!(index.search(" "), Vec::<usize>::new());
+    }
+
+    /// `match_ranges` for a query, as (start, end) pairs.
+    fn ranges(text: &str, query: &str) -> Vec<(usize, usize)> {
+        match_ranges(text, &terms(query)).into_iter().map(|range| (range.start, range.end)).collect()
+    }
+
+    #[test]
+    fn merges_overlapping_and_adjacent_matches() {
+        assert_eq!(ranges("Flying flyers fly", "FLY flying"), [(0, 6), (7, 10), (14, 17)]);
+        assert_eq!(ranges("sea-life", "sea -life"), [(0, 8)]);
+        assert_eq!(ranges("sea-life", "life"), [(4, 8)]);
+        // Byte ranges, around characters of two bytes.
+        assert_eq!(ranges("Ünderwater Über", "ü"), [(0, 2), (12, 14)]);
+        assert_eq!(ranges("teeth", "eth"), []);
+    }
+}
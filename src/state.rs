@@ -0,0 +1,32 @@
+use crate::{app::SortMode, constants::STATE_FILE, paths::state_dir, store::write_atomic};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, path::PathBuf};
+
+/// What the TUI remembers between sessions, kept in
+/// `$XDG_STATE_HOME/dreaming/state.toml`. Unlike `config.toml` this file is
+/// written by the app.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct State {
+    pub sort: SortMode,
+}
+
+impl State {
+    /// A missing or unreadable file just means nothing is remembered yet.
+    pub fn load() -> State {
+        state_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|data| toml::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let dir = state_dir().ok_or("no state directory on this platform")?;
+        std::fs::create_dir_all(&dir)?;
+        write_atomic(&dir.join(STATE_FILE), toml::to_string(self)?.as_bytes())
+    }
+}
+
+fn state_path() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join(STATE_FILE))
+}
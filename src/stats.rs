@@ -0,0 +1,415 @@
+use crate::{
+    config::{Config, GoalCount, Period},
+    dream::Dream,
+    interface::{INTENSITY_OPTIONS, STYLE_OPTIONS},
+};
+use chrono::{Datelike, Days, NaiveDate};
+use serde::Serialize;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+};
+
+/// How many words and tags are listed as most common.
+const TOP_COUNT: usize = 10;
+
+/// Words too common to say anything about a dream.
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "again", "all", "and", "are", "around", "back", "but", "could", "did", "for",
+    "from", "had", "has", "have", "her", "him", "his", "into", "its", "just", "like", "not", "off",
+    "one", "only", "our", "out", "over", "she", "some", "that", "the", "their", "them", "then",
+    "there", "they", "this", "through", "too", "very", "was", "were", "what", "when", "where",
+    "which", "while", "who", "with", "would", "you", "your",
+];
+
+/// Summary numbers over a journal, as shown on the stats screen and printed
+/// by `dreaming stats`.
+#[derive(Serialize)]
+pub struct Stats {
+    pub dreams: usize,
+    /// Nights with at least one dream.
+    pub nights: usize,
+    pub average_frequency: Option<f64>,
+    /// Every week from the first night to the last, empty ones included.
+    pub weeks: Vec<Week>,
+    pub styles: Vec<Count>,
+    pub intensities: Vec<Count>,
+    pub streaks: Streaks,
+    pub top_words: Vec<Count>,
+    pub top_tags: Vec<Count>,
+    pub goals: Vec<GoalProgress>,
+}
+
+#[derive(Serialize)]
+pub struct Week {
+    /// The Monday the week starts on.
+    pub start: NaiveDate,
+    pub dreams: usize,
+    pub average_frequency: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct Count {
+    pub name: String,
+    pub count: usize,
+}
+
+/// Runs of consecutive nights with a dream.
+#[derive(Serialize)]
+pub struct Streaks {
+    /// The run ending tonight, or last night while tonight has no dream yet.
+    pub current: usize,
+    pub longest: usize,
+    pub longest_start: Option<NaiveDate>,
+    pub longest_end: Option<NaiveDate>,
+}
+
+/// How far a goal from the configuration is in its current week or month.
+#[derive(Serialize)]
+pub struct GoalProgress {
+    pub goal: String,
+    pub done: usize,
+    pub target: usize,
+    pub per: Period,
+    /// The first night of the week or month being counted.
+    pub since: NaiveDate,
+}
+
+impl Stats {
+    /// `tonight` is the night in progress.
+    pub fn new(dreams: &[Dream], config: &Config, tonight: NaiveDate) -> Stats {
+        let cutoff = config.night_cutoff;
+        let nights: BTreeSet<NaiveDate> = dreams.iter().map(|d| d.night(cutoff)).collect();
+
+        let mut weeks: BTreeMap<NaiveDate, (usize, u32)> = BTreeMap::new();
+        if let (Some(first), Some(last)) = (nights.first(), nights.last()) {
+            let mut week = week_start(*first);
+            while week <= *last {
+                weeks.insert(week, (0, 0));
+                week = week + Days::new(7);
+            }
+        }
+        for dream in dreams {
+            let week = weeks.entry(week_start(dream.night(cutoff))).or_default();
+            week.0 += 1;
+            week.1 += dream.frequency as u32;
+        }
+
+        let tag_counts = count(dreams.iter().flat_map(|d| d.tags.iter().cloned()));
+        let word_counts = count(dreams.iter().flat_map(|d| words(&d.experience)));
+
+        Stats {
+            dreams: dreams.len(),
+            nights: nights.len(),
+            average_frequency: average(
+                dreams.iter().map(|d| d.frequency as u32).sum(),
+                dreams.len(),
+            ),
+            weeks: weeks
+                .into_iter()
+                .map(|(start, (dreams, frequency))| Week {
+                    start,
+                    dreams,
+                    average_frequency: average(frequency, dreams),
+                })
+                .collect(),
+            styles: STYLE_OPTIONS
+                .iter()
+                .map(|style| Count {
+                    name: style.to_string(),
+                    count: dreams.iter().filter(|d| d.style == *style).count(),
+                })
+                .collect(),
+            intensities: INTENSITY_OPTIONS
+                .iter()
+                .map(|intensity| Count {
+                    name: intensity.to_string(),
+                    count: dreams.iter().filter(|d| d.intensity == *intensity).count(),
+                })
+                .collect(),
+            streaks: Streaks::new(&nights, tonight),
+            top_words: top(word_counts),
+            top_tags: top(tag_counts),
+            goals: config
+                .goals
+                .iter()
+                .map(|goal| {
+                    let since = match goal.per {
+                        Period::Week => week_start(tonight),
+                        Period::Month => tonight.with_day(1).unwrap_or(tonight),
+                    };
+                    let counted = dreams.iter().filter(|dream| {
+                        (since..=tonight).contains(&dream.night(cutoff))
+                            && goal.filter.as_ref().is_none_or(|filter| filter.matches(dream, config))
+                    });
+                    let done = match goal.count {
+                        GoalCount::Nights => counted.map(|dream| dream.night(cutoff)).collect::<BTreeSet<_>>().len(),
+                        GoalCount::Dreams => counted.count(),
+                    };
+                    GoalProgress {
+                        goal: goal.to_string(),
+                        done,
+                        target: goal.target,
+                        per: goal.per,
+                        since,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Streaks {
+    pub fn new(nights: &BTreeSet<NaiveDate>, tonight: NaiveDate) -> Streaks {
+        let mut day = tonight;
+        if !nights.contains(&day) {
+            day = day - Days::new(1);
+        }
+        let mut current = 0;
+        while nights.contains(&day) {
+            current += 1;
+            day = day - Days::new(1);
+        }
+
+        let (mut longest, mut longest_start, mut longest_end) = (0, None, None);
+        let (mut run, mut run_start, mut previous): (usize, Option<NaiveDate>, Option<NaiveDate>) =
+            (0, None, None);
+        for night in nights {
+            if previous.and_then(|p| p.succ_opt()) == Some(*night) {
+                run += 1;
+            } else {
+                run = 1;
+                run_start = Some(*night);
+            }
+            if run > longest {
+                longest = run;
+                longest_start = run_start;
+                longest_end = Some(*night);
+            }
+            previous = Some(*night);
+        }
+
+        Streaks {
+            current,
+            longest,
+            longest_start,
+            longest_end,
+        }
+    }
+}
+
+fn week_start(day: NaiveDate) -> NaiveDate {
+    day - Days::new(day.weekday().num_days_from_monday() as u64)
+}
+
+fn average(total: u32, count: usize) -> Option<f64> {
+    (count > 0).then(|| total as f64 / count as f64)
+}
+
+fn count(items: impl Iterator<Item = String>) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for item in items {
+        *counts.entry(item).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// The most frequent entries, ties in alphabetical order.
+fn top(counts: BTreeMap<String, usize>) -> Vec<Count> {
+    let mut counts: Vec<Count> = counts
+        .into_iter()
+        .map(|(name, count)| Count { name, count })
+        .collect();
+    counts.sort_by_key(|count| Reverse(count.count));
+    counts.truncate(TOP_COUNT);
+    counts
+}
+
+/// Lowercased words of three letters or more, without stop words.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|word| word.trim_matches('\'').to_lowercase())
+        .filter(|word| word.chars().count() >= 3 && !STOP_WORDS.contains(&word.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dates::from_local,
+        dream::{Intensity, Style},
+    };
+    use chrono::NaiveDateTime;
+
+    /// A dream at `when`, local time.
+    fn dream(when: &str, frequency: u8, experience: &str, tags: &[&str]) -> Dream {
+        let date = from_local(NaiveDateTime::parse_from_str(when, "%Y-%m-%d %H:%M").unwrap()).unwrap();
+        let mut dream = Dream::new(date, Intensity::Low, Style::Lucid);
+        dream.frequency = frequency;
+        dream.experience = experience.to_string();
+        dream.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        dream
+    }
+
+    fn day(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    fn counts(counts: &[Count]) -> Vec<(&str, usize)> {
+        counts.iter().map(|count| (count.name.as_str(), count.count)).collect()
+    }
+
+    fn journal() -> Vec<Dream> {
+        vec![
+            dream("2025-06-01 23:00", 2, "The ocean, the OCEAN and a lighthouse", &["sea", "night"]),
+            // Before the noon cutoff, so still Sunday's night.
+            dream("2025-06-02 03:00", 4, "Ocean waves; swimming with whales", &["sea"]),
+            dream("2025-06-02 23:00", 3, "Whales again, 'waves'", &["sea", "whales"]),
+            dream("2025-06-17 22:00", 0, "", &[]),
+        ]
+    }
+
+    #[test]
+    fn buckets_nights_into_weeks_starting_on_monday() {
+        let stats = Stats::new(&journal(), &Config::default(), day("2025-06-20"));
+        assert_eq!(stats.dreams, 4);
+        assert_eq!(stats.nights, 3);
+        let weeks: Vec<_> = stats
+            .weeks
+            .iter()
+            .map(|week| (week.start, week.dreams, week.average_frequency))
+            .collect();
+        assert_eq!(
+            weeks,
+            [
+                (day("2025-05-26"), 2, Some(3.0)),
+                (day("2025-06-02"), 1, Some(3.0)),
+                (day("2025-06-09"), 0, None),
+                (day("2025-06-16"), 1, Some(0.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn averages_the_frequency_of_every_dream() {
+        let stats = Stats::new(&journal(), &Config::default(), day("2025-06-20"));
+        assert_eq!(stats.average_frequency, Some(2.25));
+
+        let stats = Stats::new(&[], &Config::default(), day("2025-06-20"));
+        assert_eq!(stats.average_frequency, None);
+        assert!(stats.weeks.is_empty());
+    }
+
+    #[test]
+    fn counts_top_words_without_stop_words_and_top_tags() {
+        let stats = Stats::new(&journal(), &Config::default(), day("2025-06-20"));
+        assert_eq!(
+            counts(&stats.top_words),
+            [("ocean", 3), ("waves", 2), ("whales", 2), ("lighthouse", 1), ("swimming", 1)]
+        );
+        assert_eq!(counts(&stats.top_tags), [("sea", 3), ("night", 1), ("whales", 1)]);
+    }
+
+    fn nights(days: &[&str]) -> BTreeSet<NaiveDate> {
+        days.iter().map(|text| day(text)).collect()
+    }
+
+    #[test]
+    fn counts_the_current_streak_up_to_last_night_until_tonight_has_a_dream() {
+        let nights = nights(&["2025-06-10", "2025-06-17", "2025-06-18", "2025-06-19"]);
+        assert_eq!(Streaks::new(&nights, day("2025-06-19")).current, 3);
+        // No dream yet tonight: the streak from last night still counts.
+        assert_eq!(Streaks::new(&nights, day("2025-06-20")).current, 3);
+        assert_eq!(Streaks::new(&nights, day("2025-06-21")).current, 0);
+    }
+
+    #[test]
+    fn counts_streaks_by_night_across_the_cutoff() {
+        let dreams = [
+            dream("2025-06-17 23:00", 0, "", &[]),
+            // Before noon, so the night of the 18th, a day and a half after the last.
+            dream("2025-06-19 11:30", 0, "", &[]),
+            // After noon: the night of the 20th, leaving the 19th empty.
+            dream("2025-06-20 12:30", 0, "", &[]),
+        ];
+        let streaks = Stats::new(&dreams, &Config::default(), day("2025-06-20")).streaks;
+        assert_eq!((streaks.current, streaks.longest), (1, 2));
+        assert_eq!(streaks.longest_start, Some(day("2025-06-17")));
+        assert_eq!(streaks.longest_end, Some(day("2025-06-18")));
+    }
+
+    #[test]
+    fn keeps_the_first_of_the_longest_streaks() {
+        let nights = nights(&[
+            "2025-06-01", "2025-06-02", "2025-06-03", "2025-06-10", "2025-06-11", "2025-06-20",
+            "2025-06-21", "2025-06-22",
+        ]);
+        let streaks = Streaks::new(&nights, day("2025-06-22"));
+        assert_eq!((streaks.current, streaks.longest), (3, 3));
+        assert_eq!(streaks.longest_start, Some(day("2025-06-01")));
+        assert_eq!(streaks.longest_end, Some(day("2025-06-03")));
+
+        let streaks = Streaks::new(&BTreeSet::new(), day("2025-06-22"));
+        assert_eq!((streaks.current, streaks.longest), (0, 0));
+        assert_eq!((streaks.longest_start, streaks.longest_end), (None, None));
+    }
+
+    #[test]
+    fn counts_goals_over_this_week_or_month_through_their_filter() {
+        let config: Config = toml::from_str(
+            r#"
+            [[goals]]
+            target = 5
+            per = "week"
+
+            [[goals]]
+            target = 2
+            per = "week"
+            filter = "style:nightmare"
+
+            [[goals]]
+            target = 4
+            count = "dreams"
+            per = "month"
+            filter = "tag:sea"
+
+            [[goals]]
+            target = 4
+            per = "month"
+            filter = "tag:sea"
+            "#,
+        )
+        .unwrap();
+        let nightmare = |when| {
+            let mut dream = dream(when, 0, "", &[]);
+            dream.style = Style::Nightmare;
+            dream
+        };
+        let dreams = [
+            dream("2025-05-31 23:00", 0, "", &["sea"]),
+            dream("2025-06-01 22:00", 0, "", &["sea"]),
+            dream("2025-06-15 23:00", 0, "", &["sea"]),
+            nightmare("2025-06-16 23:00"),
+            dream("2025-06-17 02:00", 0, "", &["sea"]),
+            dream("2025-06-17 03:00", 0, "", &["sea"]),
+            nightmare("2025-06-18 23:00"),
+            dream("2025-06-19 23:00", 0, "", &["sea"]),
+        ];
+        // A Thursday.
+        let stats = Stats::new(&dreams, &config, day("2025-06-19"));
+        let goals: Vec<_> = stats
+            .goals
+            .iter()
+            .map(|goal| (goal.done, goal.target, goal.since))
+            .collect();
+        assert_eq!(
+            goals,
+            [
+                (3, 5, day("2025-06-16")),
+                (2, 2, day("2025-06-16")),
+                (5, 4, day("2025-06-01")),
+                (4, 4, day("2025-06-01")),
+            ]
+        );
+    }
+}
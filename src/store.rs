@@ -0,0 +1,936 @@
+use crate::{
+    crypt::{self, Key},
+    dream::{legacy_date, legacy_timestamp, unreadable_dates, Dream},
+    schema::{self, upgrade_entry, SCHEMA_VERSION},
+};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, DatabaseName, OpenFlags};
+use std::{
+    error::Error,
+    io::Write,
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+/// A single pending mutation, addressed by the dream's id.
+pub enum Change {
+    Insert(Dream),
+    Update(Dream),
+    Delete(Uuid),
+}
+
+/// Storage backend for the journal. Mutations are staged until `flush`.
+pub trait DreamStore {
+    /// The dreams matching `filter`. Every backend loads and decodes every
+    /// entry and filters in Rust; the app keeps the whole journal in memory,
+    /// so SQLite gains per-dream writes in a transaction, not faster reads.
+    fn query(&mut self, filter: &dyn Fn(&Dream) -> bool) -> Result<Vec<Dream>, Box<dyn Error>>;
+    fn insert(&mut self, dream: &Dream) -> Result<(), Box<dyn Error>>;
+    fn update(&mut self, dream: &Dream) -> Result<(), Box<dyn Error>>;
+    fn delete(&mut self, id: &Uuid) -> Result<(), Box<dyn Error>>;
+    fn flush(&mut self) -> Result<(), Box<dyn Error>>;
+    fn rollback(&mut self) -> Result<(), Box<dyn Error>>;
+    fn path(&self) -> &Path;
+
+    /// Loads every entry that can still be read, skipping the damaged ones.
+    fn salvage(&mut self) -> Result<Vec<Dream>, Box<dyn Error>>;
+
+    /// Replaces the journal with the contents of `backup` and reloads it.
+    fn restore(&mut self, backup: &Path) -> Result<Vec<Dream>, Box<dyn Error>>;
+
+    /// Writes a consistent copy of the journal as it is on disk to `dest`.
+    fn snapshot(&mut self, dest: &Path) -> Result<(), Box<dyn Error>>;
+
+    /// Reads the journal with `key` and encrypts what is written from now
+    /// on, or writes plaintext for `None`.
+    fn set_key(&mut self, key: Option<Key>) -> Result<(), Box<dyn Error>>;
+
+    fn key(&self) -> Option<&Key>;
+
+    fn load(&mut self) -> Result<Vec<Dream>, Box<dyn Error>> {
+        self.query(&|_| true)
+    }
+
+    /// Applies every change and flushes, or leaves the store as it was on disk.
+    fn apply(&mut self, changes: &[Change]) -> Result<(), Box<dyn Error>> {
+        let result = changes
+            .iter()
+            .try_for_each(|change| match change {
+                Change::Insert(dream) => self.insert(dream),
+                Change::Update(dream) => self.update(dream),
+                Change::Delete(id) => self.delete(id),
+            })
+            .and_then(|_| self.flush());
+        if result.is_err() {
+            self.rollback()?;
+        }
+        result
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    Json,
+    Sqlite,
+}
+
+/// Opens the journal at `path`; `date_format` reads dates written by older
+/// versions while migrating.
+pub fn open_store(kind: StoreKind, path: PathBuf, date_format: &str) -> Result<Box<dyn DreamStore>, Box<dyn Error>> {
+    match kind {
+        StoreKind::Json => Ok(Box::new(JsonStore::new(path, date_format))),
+        StoreKind::Sqlite => Ok(Box::new(SqliteStore::open(path, date_format)?)),
+    }
+}
+
+/// The original format: the whole journal as one pretty-printed JSON document.
+/// The whole file may be encrypted with a passphrase.
+pub struct JsonStore {
+    path: PathBuf,
+    dreams: Vec<Dream>,
+    key: Option<Key>,
+    date_format: String,
+}
+
+impl JsonStore {
+    pub fn new(path: PathBuf, date_format: &str) -> JsonStore {
+        JsonStore {
+            path,
+            dreams: Vec::new(),
+            key: None,
+            date_format: date_format.to_string(),
+        }
+    }
+}
+
+impl DreamStore for JsonStore {
+    fn query(&mut self, filter: &dyn Fn(&Dream) -> bool) -> Result<Vec<Dream>, Box<dyn Error>> {
+        let data = match std::fs::read(&self.path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                self.dreams = Vec::new();
+                return Ok(Vec::new());
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let decoded = schema::decode(&crypt::open(&data, self.key.as_ref())?, &self.date_format)?;
+        self.dreams = decoded.dreams;
+        if decoded.version < SCHEMA_VERSION {
+            // Older journals are upgraded once and written back; the original
+            // is kept beside it, encrypted if the journal is.
+            write_atomic(
+                &premigration_path(&self.path, decoded.version as i64)?,
+                &crypt::seal_copy(&data, self.key.as_ref())?,
+            )?;
+            self.flush()?;
+        }
+        Ok(self.dreams.iter().filter(|d| filter(d)).cloned().collect())
+    }
+
+    fn insert(&mut self, dream: &Dream) -> Result<(), Box<dyn Error>> {
+        self.dreams.push(dream.clone());
+        Ok(())
+    }
+
+    fn update(&mut self, dream: &Dream) -> Result<(), Box<dyn Error>> {
+        let slot = self
+            .dreams
+            .iter_mut()
+            .find(|d| d.id == dream.id)
+            .ok_or_else(|| format!("no dream with id {}", dream.id))?;
+        *slot = dream.clone();
+        Ok(())
+    }
+
+    fn delete(&mut self, id: &Uuid) -> Result<(), Box<dyn Error>> {
+        let index = self
+            .dreams
+            .iter()
+            .position(|d| d.id == *id)
+            .ok_or_else(|| format!("no dream with id {}", id))?;
+        self.dreams.remove(index);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        let serialized = schema::encode(&self.dreams)?;
+        write_atomic(&self.path, &crypt::seal(serialized.as_bytes(), self.key.as_ref())?)
+    }
+
+    fn rollback(&mut self) -> Result<(), Box<dyn Error>> {
+        self.load().map(|_| ())
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn salvage(&mut self) -> Result<Vec<Dream>, Box<dyn Error>> {
+        let data = crypt::open(&std::fs::read(&self.path)?, self.key.as_ref())?;
+        self.dreams = salvage_json(&String::from_utf8_lossy(&data), &self.date_format);
+        Ok(self.dreams.clone())
+    }
+
+    fn restore(&mut self, backup: &Path) -> Result<Vec<Dream>, Box<dyn Error>> {
+        // Backups from before the journal was encrypted are encrypted now.
+        let data = crypt::open(&std::fs::read(backup)?, self.key.as_ref())?;
+        schema::decode(&data, &self.date_format)?;
+        write_atomic(&self.path, &crypt::seal(&data, self.key.as_ref())?)?;
+        self.load()
+    }
+
+    fn snapshot(&mut self, dest: &Path) -> Result<(), Box<dyn Error>> {
+        match std::fs::read(&self.path) {
+            Ok(data) => write_atomic(dest, &data),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn set_key(&mut self, key: Option<Key>) -> Result<(), Box<dyn Error>> {
+        self.key = key;
+        Ok(())
+    }
+
+    fn key(&self) -> Option<&Key> {
+        self.key.as_ref()
+    }
+}
+
+/// Replaces `path` so that a crash leaves either the old or the new contents:
+/// write a temp file in the same directory, fsync it, then rename over.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    let dir = parent_dir(path);
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(contents)?;
+    file.as_file().sync_all()?;
+    file.persist(path)?;
+    #[cfg(unix)]
+    std::fs::File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Parses every standalone dream object found in `data`, ignoring whatever
+/// surrounds it, so one damaged entry doesn't take the rest with it.
+fn salvage_json(data: &str, date_format: &str) -> Vec<Dream> {
+    let mut dreams = Vec::new();
+    let mut offset = 0;
+    while let Some(found) = data[offset..].find('{') {
+        let start = offset + found;
+        let mut stream =
+            serde_json::Deserializer::from_str(&data[start..]).into_iter::<serde_json::Value>();
+        let dream = stream.next().and_then(Result::ok).and_then(|mut entry| {
+            upgrade_entry(&mut entry, date_format).ok()?;
+            serde_json::from_value::<Dream>(entry).ok()
+        });
+        match dream {
+            Some(dream) => {
+                dreams.push(dream);
+                offset = start + stream.byte_offset();
+            }
+            None => offset = start + 1,
+        }
+    }
+    dreams
+}
+
+const SQLITE_SCHEMA_VERSION: i64 = 4;
+
+/// One row per dream; only the rows touched since the last flush are written.
+pub struct SqliteStore {
+    path: PathBuf,
+    conn: Connection,
+    in_transaction: bool,
+    date_format: String,
+    /// Whether the schema has been checked and migrated.
+    migrated: bool,
+    /// Rows that failed to decode when salvaging, deleted on the next flush.
+    broken: Vec<i64>,
+}
+
+impl SqliteStore {
+    /// Opens the file without reading it, so a damaged database fails on
+    /// `load` and can be recovered like a damaged JSON journal.
+    pub fn open(path: PathBuf, date_format: &str) -> Result<SqliteStore, Box<dyn Error>> {
+        Ok(SqliteStore {
+            conn: Connection::open(&path)?,
+            path,
+            in_transaction: false,
+            date_format: date_format.to_string(),
+            migrated: false,
+            broken: Vec::new(),
+        })
+    }
+
+    /// Migrates the schema the first time the database is used, keeping a
+    /// copy of an older database first.
+    fn migrate(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.migrated {
+            return Ok(());
+        }
+        let version: i64 = self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > 0 && version < SQLITE_SCHEMA_VERSION {
+            self.conn
+                .backup(DatabaseName::Main, premigration_path(&self.path, version)?, None)?;
+        }
+        migrate_sqlite(&mut self.conn, &self.date_format)?;
+        self.migrated = true;
+        Ok(())
+    }
+
+    fn begin(&mut self) -> Result<(), Box<dyn Error>> {
+        self.migrate()?;
+        if !self.in_transaction {
+            self.conn.execute_batch("BEGIN")?;
+            self.in_transaction = true;
+        }
+        Ok(())
+    }
+
+    /// Reads every row with its `seq`, keeping rows that fail to decode as
+    /// errors.
+    fn rows(&mut self) -> Result<Vec<(i64, Decoded)>, Box<dyn Error>> {
+        self.migrate()?;
+        let mut statement = self.conn.prepare(
+            "SELECT seq, id, date, intensity, experience, frequency, style, tags, created_at, updated_at
+             FROM dreams ORDER BY seq",
+        )?;
+        let rows = statement.query_map([], |row| Ok((row.get(0)?, decode_row(row))))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+/// A dream read from a row, or why it could not be.
+type Decoded = Result<Dream, String>;
+
+fn decode_row(row: &rusqlite::Row) -> Decoded {
+    let text = |index| row.get::<_, String>(index).map_err(|err| err.to_string());
+    let timestamp = |index| row.get::<_, DateTime<Utc>>(index).map_err(|err| err.to_string());
+    let (id, date, tags) = (text(1)?, text(2)?, text(7)?);
+    Ok(Dream {
+        id: id.parse().map_err(|err| format!("invalid id '{}': {}", id, err))?,
+        date: DateTime::parse_from_rfc3339(&date).map_err(|err| format!("invalid date '{}': {}", date, err))?,
+        intensity: text(3)?.parse()?,
+        experience: text(4)?,
+        frequency: row.get(5).map_err(|err| err.to_string())?,
+        style: text(6)?.parse()?,
+        tags: serde_json::from_str(&tags).map_err(|err| format!("invalid tags '{}': {}", tags, err))?,
+        created_at: timestamp(8)?,
+        updated_at: timestamp(9)?,
+    })
+}
+
+/// Brings the database up to `SQLITE_SCHEMA_VERSION`, one step at a time.
+fn migrate_sqlite(conn: &mut Connection, date_format: &str) -> Result<(), Box<dyn Error>> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > SQLITE_SCHEMA_VERSION {
+        return Err(format!("database schema version {} is newer than this build", version).into());
+    }
+    if version == SQLITE_SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    let tx = conn.transaction()?;
+    if version < 1 {
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS dreams (
+                seq INTEGER PRIMARY KEY,
+                date TEXT NOT NULL,
+                intensity TEXT NOT NULL,
+                experience TEXT NOT NULL,
+                frequency INTEGER NOT NULL,
+                style TEXT NOT NULL
+            );",
+        )?;
+    }
+    if version < 2 {
+        tx.execute_batch(
+            "ALTER TABLE dreams ADD COLUMN id TEXT;
+             ALTER TABLE dreams ADD COLUMN created_at TEXT;
+             ALTER TABLE dreams ADD COLUMN updated_at TEXT;",
+        )?;
+        let legacy: Vec<(i64, String)> = tx
+            .prepare("SELECT seq, date FROM dreams")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        let mut unreadable = Vec::new();
+        for (seq, date) in legacy {
+            let Some(created_at) = legacy_timestamp(&date, date_format) else {
+                unreadable.push(date);
+                continue;
+            };
+            tx.execute(
+                "UPDATE dreams SET id = ?1, created_at = ?2, updated_at = ?2 WHERE seq = ?3",
+                params![Uuid::new_v4().to_string(), created_at, seq],
+            )?;
+        }
+        if !unreadable.is_empty() {
+            return Err(unreadable_dates(&unreadable, date_format).into());
+        }
+        tx.execute_batch("CREATE UNIQUE INDEX dreams_id ON dreams (id);")?;
+    }
+    if version < 3 {
+        tx.execute_batch("ALTER TABLE dreams ADD COLUMN tags TEXT NOT NULL DEFAULT '[]';")?;
+    }
+    if version < 4 {
+        // Dates so far were UTC.
+        let legacy: Vec<(i64, String)> = tx
+            .prepare("SELECT seq, date FROM dreams")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        let mut unreadable = Vec::new();
+        for (seq, date) in legacy {
+            let Some(parsed) = legacy_date(&date, date_format) else {
+                unreadable.push(date);
+                continue;
+            };
+            tx.execute(
+                "UPDATE dreams SET date = ?1 WHERE seq = ?2",
+                params![parsed.to_rfc3339(), seq],
+            )?;
+        }
+        if !unreadable.is_empty() {
+            return Err(unreadable_dates(&unreadable, date_format).into());
+        }
+    }
+    tx.pragma_update(None, "user_version", SQLITE_SCHEMA_VERSION)?;
+    tx.commit()?;
+    Ok(())
+}
+
+impl DreamStore for SqliteStore {
+    fn query(&mut self, filter: &dyn Fn(&Dream) -> bool) -> Result<Vec<Dream>, Box<dyn Error>> {
+        let mut dreams = Vec::new();
+        for (_, dream) in self.rows()? {
+            let dream = dream?;
+            if filter(&dream) {
+                dreams.push(dream);
+            }
+        }
+        Ok(dreams)
+    }
+
+    fn insert(&mut self, dream: &Dream) -> Result<(), Box<dyn Error>> {
+        self.begin()?;
+        self.conn.execute(
+            "INSERT INTO dreams (id, date, intensity, experience, frequency, style, tags, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                dream.id.to_string(),
+                dream.date.to_rfc3339(),
+                dream.intensity.to_string(),
+                dream.experience,
+                dream.frequency,
+                dream.style.to_string(),
+                serde_json::to_string(&dream.tags)?,
+                dream.created_at,
+                dream.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn update(&mut self, dream: &Dream) -> Result<(), Box<dyn Error>> {
+        self.begin()?;
+        let updated = self.conn.execute(
+            "UPDATE dreams SET date = ?1, intensity = ?2, experience = ?3, frequency = ?4, style = ?5,
+             tags = ?6, created_at = ?7, updated_at = ?8 WHERE id = ?9",
+            params![
+                dream.date.to_rfc3339(),
+                dream.intensity.to_string(),
+                dream.experience,
+                dream.frequency,
+                dream.style.to_string(),
+                serde_json::to_string(&dream.tags)?,
+                dream.created_at,
+                dream.updated_at,
+                dream.id.to_string()
+            ],
+        )?;
+        if updated == 0 {
+            return Err(format!("no dream with id {}", dream.id).into());
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, id: &Uuid) -> Result<(), Box<dyn Error>> {
+        self.begin()?;
+        let deleted = self
+            .conn
+            .execute("DELETE FROM dreams WHERE id = ?1", params![id.to_string()])?;
+        if deleted == 0 {
+            return Err(format!("no dream with id {}", id).into());
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        for seq in self.broken.clone() {
+            self.begin()?;
+            self.conn.execute("DELETE FROM dreams WHERE seq = ?1", params![seq])?;
+        }
+        if self.in_transaction {
+            self.conn.execute_batch("COMMIT")?;
+            self.in_transaction = false;
+        }
+        self.broken.clear();
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.in_transaction {
+            self.conn.execute_batch("ROLLBACK")?;
+            self.in_transaction = false;
+        }
+        Ok(())
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The rows that fail to decode are deleted on the next flush.
+    fn salvage(&mut self) -> Result<Vec<Dream>, Box<dyn Error>> {
+        let mut dreams = Vec::new();
+        self.broken.clear();
+        for (seq, dream) in self.rows()? {
+            match dream {
+                Ok(dream) => dreams.push(dream),
+                Err(_) => self.broken.push(seq),
+            }
+        }
+        Ok(dreams)
+    }
+
+    fn restore(&mut self, backup: &Path) -> Result<Vec<Dream>, Box<dyn Error>> {
+        // The journal may be too damaged to restore into page by page, so the
+        // backup is checked and copied over it whole.
+        Connection::open_with_flags(backup, OpenFlags::SQLITE_OPEN_READ_ONLY)?
+            .query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))?;
+        self.conn = Connection::open_in_memory()?;
+        let copied = write_atomic(&self.path, &std::fs::read(backup)?);
+        self.conn = Connection::open(&self.path)?;
+        self.in_transaction = false;
+        self.migrated = false;
+        self.broken.clear();
+        copied?;
+        self.load()
+    }
+
+    fn snapshot(&mut self, dest: &Path) -> Result<(), Box<dyn Error>> {
+        self.conn.backup(DatabaseName::Main, dest, None)?;
+        Ok(())
+    }
+
+    fn set_key(&mut self, key: Option<Key>) -> Result<(), Box<dyn Error>> {
+        match key {
+            Some(_) => Err("only JSON journals can be encrypted; keep an SQLite journal on an encrypted disk instead".into()),
+            None => Ok(()),
+        }
+    }
+
+    fn key(&self) -> Option<&Key> {
+        None
+    }
+}
+
+/// Backups live next to the journal as `<file name>.<suffix>.bak`, newest first.
+pub fn list_backups(journal: &Path) -> Vec<PathBuf> {
+    let Some(name) = journal.file_name().and_then(|n| n.to_str()) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.", name);
+
+    let mut backups: Vec<(std::time::SystemTime, PathBuf)> = std::fs::read_dir(parent_dir(journal))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            file_name.starts_with(&prefix) && file_name.ends_with(".bak")
+        })
+        .map(|entry| {
+            let modified = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .unwrap_or(std::time::UNIX_EPOCH);
+            (modified, entry.path())
+        })
+        .collect();
+    backups.sort_by(|a, b| b.cmp(a));
+    backups.into_iter().map(|(_, path)| path).collect()
+}
+
+/// A fresh timestamped path for the next backup of `journal`, created empty.
+pub fn backup_path(journal: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    reserve(journal, &chrono::Utc::now().format("%Y%m%d-%H%M%S%.3f").to_string(), ".bak")
+}
+
+/// Creates `<journal>.<stem><suffix>`, or `<journal>.<stem>_<n><suffix>` with
+/// the first free `n` if that exists, and returns its path. The file is left
+/// empty for the caller to fill, so two copies made in quick succession never
+/// share a name. The names sort in the order they were made.
+fn reserve(journal: &Path, stem: &str, suffix: &str) -> Result<PathBuf, Box<dyn Error>> {
+    for n in 0.. {
+        let mut name = journal.as_os_str().to_owned();
+        match n {
+            0 => name.push(format!(".{}{}", stem, suffix)),
+            n => name.push(format!(".{}_{}{}", stem, n, suffix)),
+        }
+        let path = PathBuf::from(name);
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok(path),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(format!("{}: {}", path.display(), err).into()),
+        }
+    }
+    unreachable!("every counter is taken")
+}
+
+/// Deletes all but the newest `keep` backups of `journal`.
+pub fn prune_backups(journal: &Path, keep: usize) -> Result<(), Box<dyn Error>> {
+    for backup in list_backups(journal).into_iter().skip(keep) {
+        std::fs::remove_file(backup)?;
+    }
+    Ok(())
+}
+
+/// Copies a journal that failed to load aside before anything can overwrite
+/// it, encrypted with `key` if it is not already.
+pub fn preserve_corrupt(journal: &Path, key: Option<&Key>) -> Result<PathBuf, Box<dyn Error>> {
+    let data = crypt::seal_copy(&std::fs::read(journal)?, key)?;
+    let copy = reserve(journal, &format!("corrupt-{}", chrono::Utc::now().format("%Y%m%d%H%M%S")), "")?;
+    write_atomic(&copy, &data)?;
+    Ok(copy)
+}
+
+/// The copies of `journal` kept aside by upgrades (`.schema-v*`) and
+/// recovery (`.corrupt-*`), which are never rotated.
+pub fn kept_copies(journal: &Path) -> Vec<PathBuf> {
+    let Some(name) = journal.file_name().and_then(|n| n.to_str()) else {
+        return Vec::new();
+    };
+    let prefixes = [format!("{}.schema-v", name), format!("{}.corrupt-", name)];
+    let mut copies: Vec<PathBuf> = std::fs::read_dir(parent_dir(journal))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            prefixes.iter().any(|prefix| file_name.starts_with(prefix.as_str()))
+        })
+        .map(|entry| entry.path())
+        .collect();
+    copies.sort();
+    copies
+}
+
+/// Where the original of a journal upgraded from schema `version` is kept,
+/// created empty. These are not rotated with the regular backups.
+pub fn premigration_path(journal: &Path, version: i64) -> Result<PathBuf, Box<dyn Error>> {
+    let stem = format!("schema-v{}-{}", version, chrono::Utc::now().format("%Y%m%d%H%M%S"));
+    reserve(journal, &stem, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::DATE_FORMAT,
+        dream::{Intensity, Style},
+    };
+    use chrono::TimeZone;
+
+    /// Copies a fixture into a fresh directory and opens it as a JSON journal.
+    fn open_fixture(name: &str) -> (tempfile::TempDir, JsonStore, Vec<u8>) {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+        let original = std::fs::read(fixture).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.json");
+        std::fs::write(&path, &original).unwrap();
+        (dir, JsonStore::new(path, DATE_FORMAT), original)
+    }
+
+    fn premigration_copies(store: &JsonStore) -> Vec<PathBuf> {
+        std::fs::read_dir(parent_dir(store.path()))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_string_lossy().contains(".schema-v"))
+            .collect()
+    }
+
+    fn assert_current(store: &JsonStore) {
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(store.path()).unwrap()).unwrap();
+        assert_eq!(written["schema_version"], SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn loads_v0_journal() {
+        let (_dir, store, original) = open_fixture("journal-v0.json");
+        let mut store = JsonStore::new(store.path().to_path_buf(), "%d/%m/%Y %H:%M");
+        let dreams = store.load().unwrap();
+
+        assert_eq!(dreams.len(), 2);
+        assert!(dreams[0].intensity == Intensity::High);
+        assert!(dreams[0].style == Style::Lucid);
+        assert_eq!(dreams[0].experience, "Flying over a flooded city.");
+        assert_eq!(dreams[0].frequency, 3);
+        assert_eq!(dreams[0].created_at.to_rfc3339(), "2023-05-02T07:14:09+00:00");
+        assert_eq!(dreams[0].created_at, dreams[0].updated_at);
+        assert!(dreams[0].tags.is_empty());
+        assert_ne!(dreams[0].id, dreams[1].id);
+        // The second date was written with a custom `date_format`.
+        assert_eq!(dreams[1].created_at.to_rfc3339(), "2023-05-03T06:30:00+00:00");
+
+        assert_current(&store);
+        let copies = premigration_copies(&store);
+        assert_eq!(copies.len(), 1);
+        assert!(copies[0].to_string_lossy().contains(".schema-v0-"));
+        assert_eq!(std::fs::read(&copies[0]).unwrap(), original);
+
+        // Ids assigned during the migration are stable from then on.
+        let reloaded = store.load().unwrap();
+        assert_eq!(reloaded[0].id, dreams[0].id);
+        assert_eq!(premigration_copies(&store).len(), 1);
+    }
+
+    #[test]
+    fn keeps_v0_journal_with_unreadable_dates() {
+        let (_dir, mut store, original) = open_fixture("journal-v0.json");
+
+        let err = store.load().err().unwrap();
+        assert!(err.to_string().contains("'03/05/2023 06:30'"));
+        assert_eq!(std::fs::read(store.path()).unwrap(), original);
+        assert!(premigration_copies(&store).is_empty());
+    }
+
+    #[test]
+    fn loads_v1_journal() {
+        let (_dir, mut store, original) = open_fixture("journal-v1.json");
+        let dreams = store.load().unwrap();
+
+        assert_eq!(dreams.len(), 1);
+        assert_eq!(dreams[0].id.to_string(), "6f1c2d4e-8b7a-4c3e-9f21-0a5b6c7d8e9f");
+        assert!(dreams[0].intensity == Intensity::Medium);
+        assert!(dreams[0].style == Style::Recurring);
+        assert_eq!(dreams[0].updated_at.to_rfc3339(), "2024-01-16T20:02:45+00:00");
+        assert!(dreams[0].tags.is_empty());
+
+        assert_current(&store);
+        let copies = premigration_copies(&store);
+        assert_eq!(copies.len(), 1);
+        assert!(copies[0].to_string_lossy().contains(".schema-v1-"));
+        assert_eq!(std::fs::read(&copies[0]).unwrap(), original);
+    }
+
+    #[test]
+    fn loads_v2_journal() {
+        let (_dir, mut store, original) = open_fixture("journal-v2.json");
+        let dreams = store.load().unwrap();
+
+        assert_eq!(dreams.len(), 1);
+        assert!(dreams[0].style == Style::Nightmare);
+        assert!(dreams[0].tags.is_empty());
+        assert_eq!(dreams[0].date.to_rfc3339(), "2025-03-09T05:45:00+00:00");
+
+        assert_current(&store);
+        let copies = premigration_copies(&store);
+        assert_eq!(copies.len(), 1);
+        assert!(copies[0].to_string_lossy().contains(".schema-v2-"));
+        assert_eq!(std::fs::read(&copies[0]).unwrap(), original);
+    }
+
+    #[test]
+    fn loads_v3_journal() {
+        let (_dir, mut store, original) = open_fixture("journal-v3.json");
+        // The second date is in a custom format; the journal is kept as it
+        // was until that format is configured.
+        let err = store.load().err().unwrap();
+        assert!(err.to_string().contains("'21/08/2025 23:30'"));
+        assert_eq!(std::fs::read(store.path()).unwrap(), original);
+        assert!(premigration_copies(&store).is_empty());
+
+        let mut store = JsonStore::new(store.path().to_path_buf(), "%d/%m/%Y %H:%M");
+        let dreams = store.load().unwrap();
+
+        assert_eq!(dreams.len(), 2);
+        assert_eq!(dreams[0].tags, ["exam", "school"]);
+        assert_eq!(dreams[0].date.to_rfc3339(), "2025-08-21T04:10:00+00:00");
+        assert_eq!(dreams[1].date.to_rfc3339(), "2025-08-21T23:30:00+00:00");
+        assert_eq!(dreams[1].created_at.to_rfc3339(), "2025-08-21T21:35:00+00:00");
+
+        assert_current(&store);
+        let copies = premigration_copies(&store);
+        assert_eq!(copies.len(), 1);
+        assert!(copies[0].to_string_lossy().contains(".schema-v3-"));
+        assert_eq!(std::fs::read(&copies[0]).unwrap(), original);
+    }
+
+    #[test]
+    fn loads_current_journal_untouched() {
+        let (_dir, mut store, original) = open_fixture("journal-v4.json");
+        let dreams = store.load().unwrap();
+
+        assert_eq!(dreams.len(), 1);
+        assert_eq!(dreams[0].tags, ["exam", "school"]);
+        assert_eq!(dreams[0].date.to_rfc3339(), "2025-08-21T04:10:00+02:00");
+        assert_eq!(std::fs::read(store.path()).unwrap(), original);
+        assert!(premigration_copies(&store).is_empty());
+    }
+
+    #[test]
+    fn rejects_newer_journal() {
+        let (_dir, mut store, _) = open_fixture("journal-v4.json");
+        std::fs::write(store.path(), r#"{ "schema_version": 99, "dreams": [] }"#).unwrap();
+
+        let err = store.load().err().unwrap();
+        assert!(err.to_string().contains("newer"));
+    }
+
+    #[test]
+    fn encrypts_journal_with_key() {
+        let (_dir, mut store, original) = open_fixture("journal-v4.json");
+        let dreams = store.load().unwrap();
+        store.set_key(Some(Key::new("passphrase").unwrap())).unwrap();
+        store.flush().unwrap();
+
+        let written = std::fs::read(store.path()).unwrap();
+        assert!(crypt::is_encrypted(&written));
+        assert!(!written.windows(4).any(|w| w == b"exam"));
+        assert!(Key::unlock(&written, "wrong").is_err());
+
+        let mut reopened = JsonStore::new(store.path().to_path_buf(), DATE_FORMAT);
+        assert!(reopened.load().is_err());
+        reopened.set_key(Some(Key::unlock(&written, "passphrase").unwrap())).unwrap();
+        assert_eq!(reopened.load().unwrap()[0].id, dreams[0].id);
+
+        reopened.set_key(None).unwrap();
+        reopened.flush().unwrap();
+        assert_eq!(
+            schema::decode(&std::fs::read(store.path()).unwrap(), DATE_FORMAT).unwrap().dreams[0].tags,
+            schema::decode(&original, DATE_FORMAT).unwrap().dreams[0].tags
+        );
+    }
+
+    #[test]
+    fn migrates_sqlite_dates_with_date_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.db");
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE dreams (
+                    seq INTEGER PRIMARY KEY, date TEXT NOT NULL, intensity TEXT NOT NULL,
+                    experience TEXT NOT NULL, frequency INTEGER NOT NULL, style TEXT NOT NULL,
+                    id TEXT, created_at TEXT, updated_at TEXT, tags TEXT NOT NULL DEFAULT '[]'
+                );
+                INSERT INTO dreams VALUES (1, '21/08/2025 23:30', 'Low', 'A house with too many doors.',
+                    0, 'Normal', '5c6d7e8f-9a0b-4c1d-8e2f-3a4b5c6d7e8f',
+                    '2025-08-21T21:35:00Z', '2025-08-21T21:35:00Z', '[]');
+                PRAGMA user_version = 3;",
+            )
+            .unwrap();
+
+        let err = SqliteStore::open(path.clone(), DATE_FORMAT).unwrap().load().err().unwrap();
+        assert!(err.to_string().contains("'21/08/2025 23:30'"));
+
+        let dreams = SqliteStore::open(path, "%d/%m/%Y %H:%M").unwrap().load().unwrap();
+        assert_eq!(dreams[0].date.to_rfc3339(), "2025-08-21T23:30:00+00:00");
+    }
+
+    #[test]
+    fn copies_made_together_get_their_own_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = dir.path().join("journal.json");
+        std::fs::write(&journal, "[]").unwrap();
+
+        let names: Vec<PathBuf> = (0..3)
+            .map(|_| reserve(&journal, "20250314-073000.000", ".bak").unwrap())
+            .collect();
+        let file_names: Vec<String> = names
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            file_names,
+            [
+                "journal.json.20250314-073000.000.bak",
+                "journal.json.20250314-073000.000_1.bak",
+                "journal.json.20250314-073000.000_2.bak"
+            ]
+        );
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(sorted, names);
+
+        let corrupt = [preserve_corrupt(&journal, None).unwrap(), preserve_corrupt(&journal, None).unwrap()];
+        assert_ne!(corrupt[0], corrupt[1]);
+        assert_eq!(kept_copies(&journal).len(), 2);
+    }
+
+    fn sqlite_dream(night: u32) -> Dream {
+        let date = chrono::FixedOffset::east_opt(0)
+            .unwrap()
+            .with_ymd_and_hms(2025, 8, night, 4, 0, 0)
+            .unwrap();
+        Dream::new(date, Intensity::Low, Style::Normal)
+    }
+
+    #[test]
+    fn salvages_sqlite_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.db");
+        let (kept, damaged) = (sqlite_dream(20), sqlite_dream(21));
+        let mut store = SqliteStore::open(path.clone(), DATE_FORMAT).unwrap();
+        store
+            .apply(&[Change::Insert(kept.clone()), Change::Insert(damaged.clone())])
+            .unwrap();
+        store
+            .conn
+            .execute("UPDATE dreams SET intensity = 'Bogus' WHERE id = ?1", params![damaged.id.to_string()])
+            .unwrap();
+
+        let mut store = SqliteStore::open(path.clone(), DATE_FORMAT).unwrap();
+        assert!(store.load().is_err());
+        let dreams = store.salvage().unwrap();
+        assert_eq!(dreams.len(), 1);
+        assert_eq!(dreams[0].id, kept.id);
+        store.apply(&[]).unwrap();
+
+        let mut reopened = SqliteStore::open(path, DATE_FORMAT).unwrap();
+        let dreams = reopened.load().unwrap();
+        assert_eq!(dreams.len(), 1);
+        assert_eq!(dreams[0].id, kept.id);
+    }
+
+    #[test]
+    fn damaged_sqlite_journal_fails_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.db");
+        let backup = dir.path().join("journal.db.bak");
+        let dream = sqlite_dream(20);
+        let mut store = SqliteStore::open(path.clone(), DATE_FORMAT).unwrap();
+        store.apply(&[Change::Insert(dream.clone())]).unwrap();
+        store.snapshot(&backup).unwrap();
+        drop(store);
+        std::fs::write(&path, b"not a database, just some bytes that are long enough").unwrap();
+
+        let mut store = open_store(StoreKind::Sqlite, path, DATE_FORMAT).unwrap();
+        assert!(store.load().is_err());
+        let dreams = store.restore(&backup).unwrap();
+        assert_eq!(dreams.len(), 1);
+        assert_eq!(dreams[0].id, dream.id);
+    }
+}